@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS private.order_items;
+DROP TABLE IF EXISTS private.orders;
+DROP TYPE IF EXISTS private.order_status;
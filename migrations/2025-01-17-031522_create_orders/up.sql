@@ -0,0 +1,25 @@
+-- Your SQL goes here
+CREATE TYPE private.order_status AS ENUM ('pending', 'approved', 'fulfilled', 'cancelled');
+
+CREATE TABLE private.orders (
+    uuid UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_uuid UUID NOT NULL REFERENCES private.users(uuid),
+    status private.order_status NOT NULL DEFAULT 'pending',
+    total_cost INT4 NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE private.order_items (
+    id SERIAL PRIMARY KEY,
+    order_uuid UUID NOT NULL REFERENCES private.orders(uuid) ON DELETE CASCADE,
+    product_uuid UUID NOT NULL REFERENCES private.products(uuid),
+    quantity INT4 NOT NULL CHECK (quantity > 0),
+    unit_cost INT4 NOT NULL
+);
+
+SELECT diesel_manage_updated_at('private.orders');
+
+CREATE INDEX idx_orders_user_uuid ON private.orders(user_uuid);
+CREATE INDEX idx_orders_status ON private.orders(status);
+CREATE INDEX idx_order_items_order ON private.order_items(order_uuid);
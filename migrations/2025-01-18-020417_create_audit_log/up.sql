@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TABLE private.audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    actor_uuid UUID NOT NULL,
+    action TEXT NOT NULL,
+    target_type TEXT NOT NULL,
+    target_id TEXT NOT NULL,
+    before JSONB,
+    after JSONB,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_audit_log_actor ON private.audit_log(actor_uuid);
+CREATE INDEX idx_audit_log_target ON private.audit_log(target_type, target_id);
+CREATE INDEX idx_audit_log_action ON private.audit_log(action);
+CREATE INDEX idx_audit_log_created_at ON private.audit_log(created_at);
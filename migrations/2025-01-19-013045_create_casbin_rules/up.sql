@@ -0,0 +1,32 @@
+-- Your SQL goes here
+CREATE TABLE private.casbin_rules (
+    id SERIAL PRIMARY KEY,
+    ptype TEXT NOT NULL,
+    v0 TEXT NOT NULL DEFAULT '',
+    v1 TEXT NOT NULL DEFAULT '',
+    v2 TEXT NOT NULL DEFAULT '',
+    v3 TEXT NOT NULL DEFAULT '',
+    v4 TEXT NOT NULL DEFAULT '',
+    v5 TEXT NOT NULL DEFAULT '',
+    UNIQUE (ptype, v0, v1, v2, v3, v4, v5)
+);
+
+-- Seed with the policies previously shipped in role_policy.csv
+INSERT INTO private.casbin_rules (ptype, v0, v1, v2) VALUES
+    ('p', 'authenticated_user', 'authenticated_group', '(GET)|(PATCH)|(POST)|(DELETE)'),
+    ('p', 'authenticated_staff', 'authenticated_group', '(GET)|(PATCH)|(POST)|(DELETE)'),
+    ('p', 'authenticated_staff', 'staff_restricted_group', '(GET)|(PATCH)|(POST)|(DELETE)');
+
+INSERT INTO private.casbin_rules (ptype, v0, v1) VALUES
+    ('g3', '/auth/*', 'publicAction'),
+    ('g3', '/ws/*', 'publicAction'),
+    ('g3', '/uploads/*', 'publicAction'),
+    ('g2', '/me/*', 'authenticated_group'),
+    ('g2', '/products/*', 'authenticated_group'),
+    ('g2', '/orders/*', 'authenticated_group'),
+    ('g2', '/users/*', 'staff_restricted_group'),
+    ('g2', '/inventory/*', 'staff_restricted_group'),
+    ('g2', '/audit/*', 'staff_restricted_group'),
+    ('g2', '/admin/*', 'staff_restricted_group'),
+    ('g', 'User', 'authenticated_user'),
+    ('g', 'Admin', 'authenticated_staff');
@@ -0,0 +1,13 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM private.casbin_rules
+WHERE (ptype = 'p' AND v0 IN ('authenticated_volunteer', 'authenticated_auditor'))
+   OR (ptype = 'g2' AND v0 = '/inventory/orders/*' AND v1 = 'counter_group')
+   OR (ptype = 'g' AND v0 IN ('Volunteer', 'Auditor'));
+
+-- Postgres cannot drop enum values, so rebuild the type without them
+UPDATE private.users SET role = 'user' WHERE role IN ('volunteer', 'auditor');
+ALTER TYPE private.account_type RENAME TO account_type_old;
+CREATE TYPE private.account_type AS ENUM ('user', 'admin');
+ALTER TABLE private.users
+    ALTER COLUMN role TYPE private.account_type USING role::text::private.account_type;
+DROP TYPE private.account_type_old;
@@ -0,0 +1,18 @@
+-- Your SQL goes here
+ALTER TYPE private.account_type ADD VALUE IF NOT EXISTS 'volunteer';
+ALTER TYPE private.account_type ADD VALUE IF NOT EXISTS 'auditor';
+
+-- Volunteers run the shop counter: their own account plus the staff order workflow.
+-- Auditors can read every staff resource but cannot change anything outside their own account.
+INSERT INTO private.casbin_rules (ptype, v0, v1, v2) VALUES
+    ('p', 'authenticated_volunteer', 'authenticated_group', '(GET)|(PATCH)|(POST)|(DELETE)'),
+    ('p', 'authenticated_volunteer', 'counter_group', '(GET)|(POST)'),
+    ('p', 'authenticated_auditor', 'authenticated_group', '(GET)|(PATCH)|(POST)|(DELETE)'),
+    ('p', 'authenticated_auditor', 'staff_restricted_group', '(GET)')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO private.casbin_rules (ptype, v0, v1) VALUES
+    ('g2', '/inventory/orders/*', 'counter_group'),
+    ('g', 'Volunteer', 'authenticated_volunteer'),
+    ('g', 'Auditor', 'authenticated_auditor')
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,24 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS categories_search_terms ON private.categories;
+DROP TRIGGER IF EXISTS tags_search_terms ON private.tags;
+DROP TRIGGER IF EXISTS product_tags_search_terms ON private.product_tags;
+DROP TRIGGER IF EXISTS products_category_search_terms ON private.products;
+DROP FUNCTION IF EXISTS private.category_changed();
+DROP FUNCTION IF EXISTS private.tag_renamed();
+DROP FUNCTION IF EXISTS private.product_tags_changed();
+DROP FUNCTION IF EXISTS private.product_category_changed();
+DROP FUNCTION IF EXISTS private.refresh_product_search_terms(UUID);
+
+DROP INDEX IF EXISTS private.products_search_idx;
+ALTER TABLE private.products DROP COLUMN search_vector;
+ALTER TABLE private.products ADD COLUMN search_vector tsvector NOT NULL GENERATED ALWAYS AS (
+    setweight(to_tsvector('english', coalesce(title,'')), 'A') ||
+    setweight(to_tsvector('english', coalesce(description,'')), 'B')
+) STORED;
+CREATE INDEX products_search_idx ON private.products USING GIN (search_vector);
+
+DROP INDEX IF EXISTS private.idx_products_category;
+ALTER TABLE private.products DROP COLUMN search_terms, DROP COLUMN category_id;
+DROP TABLE IF EXISTS private.product_tags;
+DROP TABLE IF EXISTS private.tags;
+DROP TABLE IF EXISTS private.categories;
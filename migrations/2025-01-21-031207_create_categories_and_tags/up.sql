@@ -0,0 +1,120 @@
+-- Your SQL goes here
+CREATE TABLE private.categories (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    parent_id INT4 REFERENCES private.categories(id) ON DELETE RESTRICT
+);
+
+CREATE TABLE private.tags (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE private.product_tags (
+    product_uuid UUID NOT NULL REFERENCES private.products(uuid) ON DELETE CASCADE,
+    tag_id INT4 NOT NULL REFERENCES private.tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (product_uuid, tag_id)
+);
+
+CREATE INDEX idx_product_tags_tag ON private.product_tags(tag_id);
+
+ALTER TABLE private.products
+    ADD COLUMN category_id INT4 REFERENCES private.categories(id) ON DELETE SET NULL,
+    ADD COLUMN search_terms TEXT NOT NULL DEFAULT '';
+
+CREATE INDEX idx_products_category ON private.products(category_id);
+
+-- Category names (including parents) and tag names are denormalised into search_terms
+-- so they can be part of the generated search_vector
+DROP INDEX private.products_search_idx;
+ALTER TABLE private.products DROP COLUMN search_vector;
+ALTER TABLE private.products ADD COLUMN search_vector tsvector NOT NULL GENERATED ALWAYS AS (
+    setweight(to_tsvector('english', coalesce(title,'')), 'A') ||
+    setweight(to_tsvector('english', coalesce(search_terms,'')), 'A') ||
+    setweight(to_tsvector('english', coalesce(description,'')), 'B')
+) STORED;
+CREATE INDEX products_search_idx ON private.products USING GIN (search_vector);
+
+CREATE FUNCTION private.refresh_product_search_terms(product UUID) RETURNS void AS $$
+DECLARE
+    category INT4;
+    terms TEXT;
+BEGIN
+    SELECT category_id INTO category FROM private.products WHERE uuid = product;
+
+    WITH RECURSIVE ancestors AS (
+        SELECT c.name, c.parent_id FROM private.categories c WHERE c.id = category
+        UNION ALL
+        SELECT c.name, c.parent_id
+        FROM private.categories c JOIN ancestors a ON c.id = a.parent_id
+    )
+    SELECT concat_ws(' ',
+        (SELECT string_agg(name, ' ') FROM ancestors),
+        (
+            SELECT string_agg(t.name, ' ')
+            FROM private.product_tags pt JOIN private.tags t ON t.id = pt.tag_id
+            WHERE pt.product_uuid = product
+        )
+    ) INTO terms;
+
+    UPDATE private.products SET search_terms = terms WHERE uuid = product;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE FUNCTION private.product_category_changed() RETURNS trigger AS $$
+BEGIN
+    PERFORM private.refresh_product_search_terms(NEW.uuid);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER products_category_search_terms
+    AFTER INSERT OR UPDATE OF category_id ON private.products
+    FOR EACH ROW EXECUTE FUNCTION private.product_category_changed();
+
+CREATE FUNCTION private.product_tags_changed() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        PERFORM private.refresh_product_search_terms(OLD.product_uuid);
+    ELSE
+        PERFORM private.refresh_product_search_terms(NEW.product_uuid);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER product_tags_search_terms
+    AFTER INSERT OR DELETE ON private.product_tags
+    FOR EACH ROW EXECUTE FUNCTION private.product_tags_changed();
+
+CREATE FUNCTION private.tag_renamed() RETURNS trigger AS $$
+BEGIN
+    PERFORM private.refresh_product_search_terms(pt.product_uuid)
+    FROM private.product_tags pt WHERE pt.tag_id = NEW.id;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER tags_search_terms
+    AFTER UPDATE OF name ON private.tags
+    FOR EACH ROW EXECUTE FUNCTION private.tag_renamed();
+
+CREATE FUNCTION private.category_changed() RETURNS trigger AS $$
+BEGIN
+    PERFORM private.refresh_product_search_terms(p.uuid)
+    FROM private.products p
+    WHERE p.category_id IN (
+        WITH RECURSIVE descendants AS (
+            SELECT NEW.id AS id
+            UNION ALL
+            SELECT c.id FROM private.categories c JOIN descendants d ON c.parent_id = d.id
+        )
+        SELECT id FROM descendants
+    );
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER categories_search_terms
+    AFTER UPDATE OF name, parent_id ON private.categories
+    FOR EACH ROW EXECUTE FUNCTION private.category_changed();
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS private.idx_products_archived_at;
+ALTER TABLE private.products DROP COLUMN archived_at;
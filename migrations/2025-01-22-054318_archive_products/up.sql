@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE private.products ADD COLUMN archived_at TIMESTAMP;
+
+CREATE INDEX idx_products_archived_at ON private.products(archived_at);
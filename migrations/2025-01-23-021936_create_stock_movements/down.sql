@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE private.products ALTER COLUMN stock DROP DEFAULT;
+DROP TABLE IF EXISTS private.stock_movements;
+DROP TYPE IF EXISTS private.stock_movement_kind;
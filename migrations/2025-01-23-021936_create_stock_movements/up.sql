@@ -0,0 +1,24 @@
+-- Your SQL goes here
+CREATE TYPE private.stock_movement_kind AS ENUM ('restock', 'redemption', 'adjustment', 'write_off', 'cancellation');
+
+CREATE TABLE private.stock_movements (
+    id BIGSERIAL PRIMARY KEY,
+    product_uuid UUID NOT NULL REFERENCES private.products(uuid) ON DELETE CASCADE,
+    actor_uuid UUID,
+    kind private.stock_movement_kind NOT NULL,
+    delta INT4 NOT NULL CHECK (delta <> 0),
+    reason TEXT,
+    order_uuid UUID REFERENCES private.orders(uuid),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_stock_movements_product ON private.stock_movements(product_uuid, created_at);
+
+-- Existing stock levels become the first entry of each product's ledger
+INSERT INTO private.stock_movements (product_uuid, kind, delta, reason)
+SELECT uuid, 'adjustment', stock, 'Opening balance'
+FROM private.products
+WHERE stock <> 0;
+
+-- Stock is only ever changed through movements, new products start empty
+ALTER TABLE private.products ALTER COLUMN stock SET DEFAULT 0;
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE private.products DROP COLUMN low_stock_alerted_at, DROP COLUMN reorder_threshold;
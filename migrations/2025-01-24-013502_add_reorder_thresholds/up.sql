@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE private.products
+    ADD COLUMN reorder_threshold INT4 NOT NULL DEFAULT 0 CHECK (reorder_threshold >= 0),
+    ADD COLUMN low_stock_alerted_at TIMESTAMP;
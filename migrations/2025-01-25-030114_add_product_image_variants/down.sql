@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE private.products DROP COLUMN thumbnail_path, DROP COLUMN card_path;
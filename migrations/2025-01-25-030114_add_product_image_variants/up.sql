@@ -0,0 +1,7 @@
+-- Your SQL goes here
+ALTER TABLE private.products
+    ADD COLUMN card_path TEXT NOT NULL DEFAULT '',
+    ADD COLUMN thumbnail_path TEXT NOT NULL DEFAULT '';
+
+-- Images uploaded before variants existed only have the full size
+UPDATE private.products SET card_path = image_path, thumbnail_path = image_path;
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM private.casbin_rules
+WHERE ptype = 'g2' AND v0 = '/tasks/*';
+
+DROP TABLE private.task_submissions;
+DROP TABLE private.tasks;
+DROP TYPE private.submission_status;
@@ -0,0 +1,43 @@
+-- Your SQL goes here
+CREATE TYPE private.submission_status AS ENUM ('claimed', 'submitted', 'approved', 'rejected');
+
+CREATE TABLE private.tasks (
+    uuid UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    title TEXT NOT NULL,
+    description TEXT NOT NULL,
+    reward INT4 NOT NULL CHECK (reward > 0),
+    capacity INT4 NOT NULL CHECK (capacity > 0),
+    deadline TIMESTAMP NOT NULL,
+    created_by UUID NOT NULL REFERENCES private.users(uuid),
+    closed_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- A resident claims a task once, rejected claims free their slot but cannot be retried
+CREATE TABLE private.task_submissions (
+    uuid UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    task_uuid UUID NOT NULL REFERENCES private.tasks(uuid) ON DELETE CASCADE,
+    user_uuid UUID NOT NULL REFERENCES private.users(uuid) ON DELETE CASCADE,
+    status private.submission_status NOT NULL DEFAULT 'claimed',
+    note TEXT,
+    photo_path TEXT,
+    review_reason TEXT,
+    reviewed_by UUID REFERENCES private.users(uuid),
+    claimed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    submitted_at TIMESTAMP,
+    reviewed_at TIMESTAMP,
+    UNIQUE (task_uuid, user_uuid)
+);
+
+SELECT diesel_manage_updated_at('private.tasks');
+
+CREATE INDEX idx_tasks_deadline ON private.tasks(deadline);
+CREATE INDEX idx_task_submissions_status ON private.task_submissions(status);
+CREATE INDEX idx_task_submissions_user ON private.task_submissions(user_uuid);
+
+-- Residents browse and claim under /tasks, staff post and review under /inventory/tasks,
+-- which the existing /inventory/* grant already covers
+INSERT INTO private.casbin_rules (ptype, v0, v1) VALUES
+    ('g2', '/tasks/*', 'authenticated_group')
+ON CONFLICT DO NOTHING;
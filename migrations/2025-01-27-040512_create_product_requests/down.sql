@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM private.casbin_rules
+WHERE ptype = 'g2' AND v0 = '/product-requests/*';
+
+DROP TABLE private.product_request_votes;
+DROP TABLE private.product_requests;
+DROP TYPE private.product_request_status;
@@ -0,0 +1,34 @@
+-- Your SQL goes here
+CREATE TYPE private.product_request_status AS ENUM ('open', 'approved', 'rejected', 'fulfilled');
+
+CREATE TABLE private.product_requests (
+    uuid UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_uuid UUID NOT NULL REFERENCES private.users(uuid) ON DELETE CASCADE,
+    title TEXT NOT NULL,
+    description TEXT NOT NULL,
+    quantity INT4 NOT NULL CHECK (quantity > 0),
+    status private.product_request_status NOT NULL DEFAULT 'open',
+    review_reason TEXT,
+    reviewed_by UUID REFERENCES private.users(uuid),
+    -- Set once the request has been turned into a catalogue product
+    product_uuid UUID REFERENCES private.products(uuid) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE private.product_request_votes (
+    request_uuid UUID NOT NULL REFERENCES private.product_requests(uuid) ON DELETE CASCADE,
+    user_uuid UUID NOT NULL REFERENCES private.users(uuid) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (request_uuid, user_uuid)
+);
+
+SELECT diesel_manage_updated_at('private.product_requests');
+
+CREATE INDEX idx_product_requests_status ON private.product_requests(status);
+CREATE INDEX idx_product_requests_user ON private.product_requests(user_uuid);
+
+-- Residents request and vote under /product-requests, staff triage under /inventory
+INSERT INTO private.casbin_rules (ptype, v0, v1) VALUES
+    ('g2', '/product-requests/*', 'authenticated_group')
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM private.casbin_rules
+WHERE ptype = 'g2' AND v0 = '/auctions/*';
+
+DROP TABLE private.auction_bids;
+DROP TABLE private.auctions;
+DROP TYPE private.auction_status;
+
+ALTER TABLE private.wallets DROP COLUMN held;
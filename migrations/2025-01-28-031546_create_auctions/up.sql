@@ -0,0 +1,41 @@
+-- Your SQL goes here
+-- Points held for a leading bid still count towards the balance but cannot be spent
+ALTER TABLE private.wallets
+    ADD COLUMN held INT4 NOT NULL DEFAULT 0 CHECK (held >= 0 AND held <= balance);
+
+CREATE TYPE private.auction_status AS ENUM ('open', 'settled', 'unsold', 'cancelled');
+
+CREATE TABLE private.auctions (
+    uuid UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    title TEXT NOT NULL,
+    description TEXT NOT NULL,
+    reserve_price INT4 NOT NULL CHECK (reserve_price >= 0),
+    starts_at TIMESTAMP NOT NULL,
+    ends_at TIMESTAMP NOT NULL,
+    status private.auction_status NOT NULL DEFAULT 'open',
+    high_bid INT4,
+    high_bidder UUID REFERENCES private.users(uuid),
+    created_by UUID NOT NULL REFERENCES private.users(uuid),
+    settled_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CHECK (ends_at > starts_at)
+);
+
+CREATE TABLE private.auction_bids (
+    id BIGSERIAL PRIMARY KEY,
+    auction_uuid UUID NOT NULL REFERENCES private.auctions(uuid) ON DELETE CASCADE,
+    user_uuid UUID NOT NULL REFERENCES private.users(uuid) ON DELETE CASCADE,
+    amount INT4 NOT NULL CHECK (amount > 0),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+SELECT diesel_manage_updated_at('private.auctions');
+
+CREATE INDEX idx_auctions_status_ends_at ON private.auctions(status, ends_at);
+CREATE INDEX idx_auction_bids_auction ON private.auction_bids(auction_uuid, created_at);
+
+-- Residents bid under /auctions, staff list and cancel under /inventory
+INSERT INTO private.casbin_rules (ptype, v0, v1) VALUES
+    ('g2', '/auctions/*', 'authenticated_group')
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE private.credit_rule_runs;
+DROP TABLE private.credit_rules;
+DROP TYPE private.credit_rule_kind;
@@ -0,0 +1,31 @@
+-- Your SQL goes here
+CREATE TYPE private.credit_rule_kind AS ENUM ('monthly', 'birthday', 'once');
+
+-- Rules only ever credit active residents, optionally narrowed to one school or dorm bunk
+CREATE TABLE private.credit_rules (
+    uuid UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL,
+    kind private.credit_rule_kind NOT NULL,
+    amount INT4 NOT NULL CHECK (amount > 0),
+    day_of_month INT4 CHECK (day_of_month BETWEEN 1 AND 28),
+    run_at TIMESTAMP,
+    school TEXT,
+    bunk TEXT,
+    paused_at TIMESTAMP,
+    created_by UUID NOT NULL REFERENCES private.users(uuid),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CHECK (kind <> 'monthly' OR day_of_month IS NOT NULL),
+    CHECK (kind <> 'once' OR run_at IS NOT NULL)
+);
+
+-- One row per credit paid out. The primary key is what stops a period being paid twice
+CREATE TABLE private.credit_rule_runs (
+    rule_uuid UUID NOT NULL REFERENCES private.credit_rules(uuid) ON DELETE CASCADE,
+    user_uuid UUID NOT NULL REFERENCES private.users(uuid) ON DELETE CASCADE,
+    period TEXT NOT NULL,
+    credited_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (rule_uuid, user_uuid, period)
+);
+
+SELECT diesel_manage_updated_at('private.credit_rules');
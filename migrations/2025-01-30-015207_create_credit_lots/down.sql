@@ -0,0 +1,14 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE private.credit_rules DROP COLUMN expires_in_days;
+
+DROP TABLE private.order_credit_lots;
+DROP TABLE private.credit_lots;
+
+-- Enum values cannot be dropped, so expiries are kept as debits in a rebuilt type
+UPDATE private.transactions SET transaction_type = 'debit' WHERE transaction_type = 'expiry';
+ALTER TYPE private.transaction_type RENAME TO transaction_type_old;
+CREATE TYPE private.transaction_type AS ENUM ('debit', 'credit');
+ALTER TABLE private.transactions
+    ALTER COLUMN transaction_type TYPE private.transaction_type
+    USING transaction_type::text::private.transaction_type;
+DROP TYPE private.transaction_type_old;
@@ -0,0 +1,31 @@
+-- Your SQL goes here
+ALTER TYPE private.transaction_type ADD VALUE 'expiry';
+
+-- Every credit opens a lot, debits use up the oldest lots first.
+-- The remaining points across a wallet's lots always add up to its balance
+CREATE TABLE private.credit_lots (
+    id BIGSERIAL PRIMARY KEY,
+    wallet_id INT4 NOT NULL REFERENCES private.wallets(id) ON DELETE CASCADE,
+    amount INT4 NOT NULL CHECK (amount > 0),
+    remaining INT4 NOT NULL CHECK (remaining >= 0 AND remaining <= amount),
+    expires_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_credit_lots_open ON private.credit_lots(wallet_id, created_at) WHERE remaining > 0;
+CREATE INDEX idx_credit_lots_expiry ON private.credit_lots(expires_at) WHERE remaining > 0;
+
+-- Points an order drew from each lot, so a refund can put them back where they came from
+CREATE TABLE private.order_credit_lots (
+    order_uuid UUID NOT NULL REFERENCES private.orders(uuid) ON DELETE CASCADE,
+    lot_id INT8 NOT NULL REFERENCES private.credit_lots(id) ON DELETE CASCADE,
+    amount INT4 NOT NULL CHECK (amount > 0),
+    PRIMARY KEY (order_uuid, lot_id)
+);
+
+-- Points earned before lots existed never expire
+INSERT INTO private.credit_lots (wallet_id, amount, remaining)
+SELECT id, balance, balance FROM private.wallets WHERE balance > 0;
+
+ALTER TABLE private.credit_rules
+    ADD COLUMN expires_in_days INT4 CHECK (expires_in_days > 0);
@@ -0,0 +1,114 @@
+use crate::backend::wallet::{debit_wallet, release_funds};
+use crate::models::auctions::{Auction, AuctionStatus};
+use crate::models::wallet::Wallet;
+use crate::req_res::AppError;
+use crate::schema::private;
+use crate::websocket::{emit_auction_closed, emit_balance_changed};
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel_async::pooled_connection::bb8::Pool;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use log::{error, info};
+use socketioxide::SocketIo;
+use std::time::Duration;
+use uuid::Uuid;
+
+const SETTLE_INTERVAL: Duration = Duration::from_secs(30);
+
+pub async fn lock_auction(conn: &mut AsyncPgConnection, uid: Uuid) -> Result<Auction, AppError> {
+    private::auctions::table
+        .find(uid)
+        .select(Auction::as_select())
+        .for_update()
+        .first::<Auction>(conn)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)
+}
+
+/// Returns the current high bid, if any, to the bidder's spendable balance.
+/// Callers are expected to run this inside a database transaction.
+pub async fn release_high_bid(
+    conn: &mut AsyncPgConnection,
+    auction: &Auction,
+) -> Result<(), AppError> {
+    if let (Some(bidder), Some(bid)) = (auction.high_bidder, auction.high_bid) {
+        release_funds(conn, bidder, bid).await?;
+    }
+    Ok(())
+}
+
+/// Periodically closes auctions past their end time. Runs until the process exits.
+pub async fn run_auction_settler(pool: Pool<AsyncPgConnection>, io: SocketIo) {
+    let mut interval = tokio::time::interval(SETTLE_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = settle_due_auctions(&pool, &io).await {
+            error!("Auction settlement failed: {:?}", e);
+        }
+    }
+}
+
+async fn settle_due_auctions(
+    pool: &Pool<AsyncPgConnection>,
+    io: &SocketIo,
+) -> Result<(), AppError> {
+    let mut con = pool.get().await?;
+    let due = private::auctions::table
+        .filter(private::auctions::status.eq(AuctionStatus::Open))
+        .filter(private::auctions::ends_at.le(Utc::now().naive_utc()))
+        .select(private::auctions::uuid)
+        .load::<Uuid>(&mut con)
+        .await?;
+
+    // Each auction settles on its own, so one failure does not hold up the rest
+    for uid in due {
+        let settled = con
+            .transaction(|conn| async move { settle_auction(conn, uid).await }.scope_boxed())
+            .await;
+        match settled {
+            Ok(Some((auction, wallet))) => {
+                info!("Auction {} closed as {:?}", auction.uuid, auction.status);
+                emit_auction_closed(io, auction.uuid, auction.status, auction.high_bid);
+                if let Some(wallet) = wallet {
+                    emit_balance_changed(io, wallet.user_uuid, wallet.balance);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => error!("Unable to settle auction {}: {:?}", uid, e),
+        }
+    }
+    Ok(())
+}
+
+/// Charges the winner if the reserve was met, otherwise closes the auction unsold.
+/// Returns `None` if the auction was already closed by someone else.
+async fn settle_auction(
+    conn: &mut AsyncPgConnection,
+    uid: Uuid,
+) -> Result<Option<(Auction, Option<Wallet>)>, AppError> {
+    let auction = lock_auction(conn, uid).await?;
+    if auction.status != AuctionStatus::Open {
+        return Ok(None);
+    }
+
+    release_high_bid(conn, &auction).await?;
+    let (status, wallet) = match (auction.high_bidder, auction.high_bid) {
+        (Some(winner), Some(bid)) if bid >= auction.reserve_price => {
+            let wallet = debit_wallet(conn, winner, bid, &format!("Auction {}", uid), None).await?;
+            (AuctionStatus::Settled, Some(wallet))
+        }
+        _ => (AuctionStatus::Unsold, None),
+    };
+
+    let auction = diesel::update(private::auctions::table.find(uid))
+        .set((
+            private::auctions::status.eq(status),
+            private::auctions::settled_at.eq(diesel::dsl::now),
+        ))
+        .returning(Auction::as_returning())
+        .get_result::<Auction>(conn)
+        .await?;
+    Ok(Some((auction, wallet)))
+}
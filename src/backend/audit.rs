@@ -0,0 +1,70 @@
+use crate::models::audit::AuditAction;
+use crate::req_res::AppError;
+use crate::schema::private;
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+/// Fields that must never end up in the audit log.
+const REDACTED_FIELDS: [&str; 2] = ["password", "search_vector"];
+
+/// Serializes an entity for the audit log with sensitive fields removed.
+pub fn snapshot<T: Serialize>(value: &T) -> Option<Value> {
+    let mut value = serde_json::to_value(value).ok()?;
+    if let Value::Object(map) = &mut value {
+        for field in REDACTED_FIELDS {
+            map.remove(field);
+        }
+    }
+    Some(value)
+}
+
+/// Records a staff action. When both snapshots are objects only the fields that
+/// changed are kept, so updates read as a before/after diff.
+pub async fn record_audit(
+    conn: &mut AsyncPgConnection,
+    actor: Uuid,
+    action: AuditAction,
+    target_id: &str,
+    before: Option<Value>,
+    after: Option<Value>,
+) -> Result<(), AppError> {
+    let (before, after) = match (before, after) {
+        (Some(Value::Object(before)), Some(Value::Object(after))) => {
+            let (before, after) = diff(before, after);
+            (Some(Value::Object(before)), Some(Value::Object(after)))
+        }
+        other => other,
+    };
+
+    diesel::insert_into(private::audit_log::table)
+        .values((
+            private::audit_log::actor_uuid.eq(actor),
+            private::audit_log::action.eq(action.as_str()),
+            private::audit_log::target_type.eq(action.target_type()),
+            private::audit_log::target_id.eq(target_id),
+            private::audit_log::before.eq(before),
+            private::audit_log::after.eq(after),
+        ))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+fn diff(
+    mut before: Map<String, Value>,
+    mut after: Map<String, Value>,
+) -> (Map<String, Value>, Map<String, Value>) {
+    let unchanged = before
+        .iter()
+        .filter(|(key, value)| after.get(*key) == Some(*value))
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<String>>();
+    for key in unchanged {
+        before.remove(&key);
+        after.remove(&key);
+    }
+    (before, after)
+}
@@ -0,0 +1,126 @@
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use crate::schema::private;
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Loads the tag names of every given product, keyed by product.
+pub async fn load_tags(
+    conn: &mut AsyncPgConnection,
+    products: &[Uuid],
+) -> Result<HashMap<Uuid, Vec<String>>, AppError> {
+    let rows = private::product_tags::table
+        .inner_join(private::tags::table)
+        .filter(private::product_tags::product_uuid.eq_any(products))
+        .select((private::product_tags::product_uuid, private::tags::name))
+        .order(private::tags::name.asc())
+        .load::<(Uuid, String)>(conn)
+        .await?;
+
+    let mut tags: HashMap<Uuid, Vec<String>> = HashMap::new();
+    for (product, tag) in rows {
+        tags.entry(product).or_default().push(tag);
+    }
+    Ok(tags)
+}
+
+/// Replaces the tags of a product, creating any tag that does not exist yet.
+pub async fn set_product_tags(
+    conn: &mut AsyncPgConnection,
+    product: Uuid,
+    names: &[String],
+) -> Result<(), AppError> {
+    diesel::delete(private::product_tags::table)
+        .filter(private::product_tags::product_uuid.eq(product))
+        .execute(conn)
+        .await?;
+    if names.is_empty() {
+        return Ok(());
+    }
+
+    // Existing tags are left untouched, an update would refresh the search terms
+    // of every product carrying them
+    diesel::insert_into(private::tags::table)
+        .values(
+            names
+                .iter()
+                .map(|name| private::tags::name.eq(name))
+                .collect::<Vec<_>>(),
+        )
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .await?;
+    let tag_ids = private::tags::table
+        .filter(private::tags::name.eq_any(names))
+        .select(private::tags::id)
+        .load::<i32>(conn)
+        .await?;
+
+    diesel::insert_into(private::product_tags::table)
+        .values(
+            tag_ids
+                .into_iter()
+                .map(|tag_id| {
+                    (
+                        private::product_tags::product_uuid.eq(product),
+                        private::product_tags::tag_id.eq(tag_id),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Fails with a validation error when the category does not exist.
+pub async fn ensure_category(conn: &mut AsyncPgConnection, id: i32) -> Result<(), AppError> {
+    let exists = diesel::select(diesel::dsl::exists(
+        private::categories::table.filter(private::categories::id.eq(id)),
+    ))
+    .get_result::<bool>(conn)
+    .await?;
+
+    if exists {
+        Ok(())
+    } else {
+        let errors = vec!["Category does not exist".to_string()];
+        Err(AppError::bad_request::<ClientErrorMessages>(
+            DataValidationError { errors }.into(),
+        ))
+    }
+}
+
+/// Returns the category and all of its subcategories.
+pub async fn category_subtree(
+    conn: &mut AsyncPgConnection,
+    root: i32,
+) -> Result<Vec<i32>, AppError> {
+    let children = child_map(conn).await?;
+    let mut subtree = vec![root];
+    let mut i = 0;
+    while let Some(id) = subtree.get(i).copied() {
+        if let Some(ids) = children.get(&id) {
+            subtree.extend(ids);
+        }
+        i += 1;
+    }
+    Ok(subtree)
+}
+
+async fn child_map(conn: &mut AsyncPgConnection) -> Result<HashMap<i32, Vec<i32>>, AppError> {
+    let rows = private::categories::table
+        .filter(private::categories::parent_id.is_not_null())
+        .select((private::categories::id, private::categories::parent_id))
+        .load::<(i32, Option<i32>)>(conn)
+        .await?;
+
+    let mut children: HashMap<i32, Vec<i32>> = HashMap::new();
+    for (id, parent) in rows {
+        if let Some(parent) = parent {
+            children.entry(parent).or_default().push(id);
+        }
+    }
+    Ok(children)
+}
@@ -0,0 +1,389 @@
+use crate::backend::wallet::credit_wallet;
+use crate::models::credit_rules::{CreditRule, CreditRuleKind};
+use crate::models::user::{AccountType, User, UserAddress};
+use crate::models::wallet::Wallet;
+use crate::req_res::AppError;
+use crate::schema::private;
+use crate::websocket::emit_balance_changed;
+use chrono::{Datelike, Months, NaiveDate, NaiveDateTime, TimeDelta, Utc};
+use diesel::prelude::*;
+use diesel_async::pooled_connection::bb8::Pool;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use log::{error, info};
+use socketioxide::SocketIo;
+use std::collections::HashSet;
+use std::time::Duration;
+use uuid::Uuid;
+
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// How long after `run_at` a one-off rule still pays out, so a restart does not
+/// lose it but residents added later do not receive it.
+const ONCE_GRACE: TimeDelta = TimeDelta::days(7);
+/// How long after a resident's birthday a birthday rule still pays out, so a resident
+/// added or given a corrected date of birth later in the year is not paid for it.
+const BIRTHDAY_GRACE: TimeDelta = TimeDelta::days(7);
+/// Formats seen in `users.dob`, which is stored as free text.
+const DOB_FORMATS: [&str; 4] = ["%d%m%Y", "%Y-%m-%d", "%d/%m/%Y", "%d-%m-%Y"];
+
+/// The periods the rule pays out for at `now`, oldest first. A period that fell before
+/// the rule was created is never paid. Monthly rules catch up on months missed while the
+/// rule was paused or the server was down, starting from `last_paid`, the latest month
+/// anyone was paid for, so residents added later are not paid for months long past.
+fn due_periods(rule: &CreditRule, now: NaiveDateTime, last_paid: Option<&str>) -> Vec<String> {
+    let today = now.date();
+    match rule.kind {
+        CreditRuleKind::Monthly => {
+            let Some(day) = rule.day_of_month else {
+                return vec![];
+            };
+            let created = rule.created_at.date();
+            let from = last_paid
+                .and_then(|period| {
+                    NaiveDate::parse_from_str(&format!("{}-01", period), "%Y-%m-%d").ok()
+                })
+                .map_or(created, |month| month.max(created));
+            let mut month = from - TimeDelta::days(i64::from(from.day0()));
+            let mut periods = vec![];
+            while month <= today {
+                let due = month.with_day(day as u32);
+                if due.is_some_and(|due| due >= created && due <= today) {
+                    periods.push(month.format("%Y-%m").to_string());
+                }
+                let Some(next) = month.checked_add_months(Months::new(1)) else {
+                    break;
+                };
+                month = next;
+            }
+            periods
+        }
+        CreditRuleKind::Birthday => vec![today.format("%Y").to_string()],
+        CreditRuleKind::Once => rule
+            .run_at
+            .filter(|at| *at <= now && now < *at + ONCE_GRACE)
+            .map(|_| vec!["once".to_string()])
+            .unwrap_or_default(),
+    }
+}
+
+fn parse_dob(dob: &str) -> Option<NaiveDate> {
+    DOB_FORMATS
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(dob.trim(), format).ok())
+}
+
+/// The resident's birthday in `year`, 29 February falls on the 28th in other years.
+fn birthday_in(dob: NaiveDate, year: i32) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt(year, dob.month(), dob.day())
+        .or_else(|| NaiveDate::from_ymd_opt(year, dob.month(), dob.day() - 1))
+}
+
+/// Whether a birthday rule created on `since` pays a resident born on `dob` at `today`.
+fn birthday_due(dob: NaiveDate, today: NaiveDate, since: NaiveDate) -> bool {
+    birthday_in(dob, today.year()).is_some_and(|birthday| {
+        birthday <= today && today - birthday <= BIRTHDAY_GRACE && birthday >= since
+    })
+}
+
+fn in_bunk(user: &User, bunk: &str) -> bool {
+    user.address
+        .clone()
+        .and_then(|address| serde_json::from_value::<UserAddress>(address).ok())
+        .is_some_and(|address| address.bunk.eq_ignore_ascii_case(bunk))
+}
+
+/// Residents the rule would credit at `now` that have not been paid yet, for each due
+/// period that still has someone to pay.
+pub async fn pending_credits(
+    conn: &mut AsyncPgConnection,
+    rule: &CreditRule,
+    now: NaiveDateTime,
+) -> Result<Vec<(String, Vec<User>)>, AppError> {
+    // Periods are zero padded, so the latest month also sorts last
+    let last_paid = private::credit_rule_runs::table
+        .filter(private::credit_rule_runs::rule_uuid.eq(rule.uuid))
+        .select(diesel::dsl::max(private::credit_rule_runs::period))
+        .first::<Option<String>>(conn)
+        .await?;
+    let periods = due_periods(rule, now, last_paid.as_deref());
+    if periods.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut query = private::users::table
+        .filter(private::users::role.eq(AccountType::User))
+        .filter(private::users::active.eq(true))
+        .select(User::as_select())
+        .order(private::users::resident_id.asc())
+        .into_boxed();
+    if let Some(school) = &rule.school {
+        query = query.filter(private::users::school.eq(school));
+    }
+    let today = now.date();
+    let users = query
+        .load::<User>(conn)
+        .await?
+        .into_iter()
+        .filter(|user| rule.bunk.as_deref().is_none_or(|bunk| in_bunk(user, bunk)))
+        .filter(|user| match rule.kind {
+            CreditRuleKind::Birthday => user
+                .dob
+                .as_deref()
+                .and_then(parse_dob)
+                .is_some_and(|dob| birthday_due(dob, today, rule.created_at.date())),
+            _ => true,
+        })
+        .collect::<Vec<User>>();
+
+    let paid = private::credit_rule_runs::table
+        .filter(private::credit_rule_runs::rule_uuid.eq(rule.uuid))
+        .filter(private::credit_rule_runs::period.eq_any(&periods))
+        .select((
+            private::credit_rule_runs::period,
+            private::credit_rule_runs::user_uuid,
+        ))
+        .load::<(String, Uuid)>(conn)
+        .await?
+        .into_iter()
+        .collect::<HashSet<(String, Uuid)>>();
+
+    Ok(periods
+        .into_iter()
+        .map(|period| {
+            let users = users
+                .iter()
+                .filter(|user| !paid.contains(&(period.clone(), user.uuid)))
+                .cloned()
+                .collect::<Vec<User>>();
+            (period, users)
+        })
+        .filter(|(_, users)| !users.is_empty())
+        .collect())
+}
+
+/// Periodically pays out every active credit rule that is due. Runs until the process exits.
+pub async fn run_credit_scheduler(pool: Pool<AsyncPgConnection>, io: SocketIo) {
+    let mut interval = tokio::time::interval(SCHEDULER_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = run_due_rules(&pool, &io).await {
+            error!("Credit rule run failed: {:?}", e);
+        }
+    }
+}
+
+async fn run_due_rules(pool: &Pool<AsyncPgConnection>, io: &SocketIo) -> Result<(), AppError> {
+    let mut con = pool.get().await?;
+    let rules = private::credit_rules::table
+        .filter(private::credit_rules::paused_at.is_null())
+        .select(CreditRule::as_select())
+        .load::<CreditRule>(&mut con)
+        .await?;
+
+    let now = Utc::now().naive_utc();
+    for rule in rules {
+        for (period, users) in pending_credits(&mut con, &rule, now).await? {
+            pay_period(&mut con, io, &rule, &period, users).await;
+        }
+    }
+    Ok(())
+}
+
+async fn pay_period(
+    con: &mut AsyncPgConnection,
+    io: &SocketIo,
+    rule: &CreditRule,
+    period: &str,
+    users: Vec<User>,
+) {
+    let mut paid = 0;
+    for user in users {
+        match pay_credit(con, rule, period, user.uuid).await {
+            Ok(Some(wallet)) => {
+                paid += 1;
+                emit_balance_changed(io, wallet.user_uuid, wallet.balance);
+            }
+            Ok(None) => {}
+            Err(e) => error!(
+                "Unable to pay credit rule {} to {}: {:?}",
+                rule.uuid, user.uuid, e
+            ),
+        }
+    }
+    if paid > 0 {
+        info!(
+            "Credit rule {} paid {} residents for {}",
+            rule.uuid, paid, period
+        );
+    }
+}
+
+/// Credits one resident for the period. The run is recorded in the same database
+/// transaction, so a period that was already paid is skipped and returns `None`.
+async fn pay_credit(
+    con: &mut AsyncPgConnection,
+    rule: &CreditRule,
+    period: &str,
+    user_uuid: Uuid,
+) -> Result<Option<Wallet>, AppError> {
+    con.transaction(|conn| {
+        async move {
+            let recorded = diesel::insert_into(private::credit_rule_runs::table)
+                .values((
+                    private::credit_rule_runs::rule_uuid.eq(rule.uuid),
+                    private::credit_rule_runs::user_uuid.eq(user_uuid),
+                    private::credit_rule_runs::period.eq(period),
+                ))
+                .on_conflict_do_nothing()
+                .execute(conn)
+                .await?;
+            if recorded == 0 {
+                return Ok(None);
+            }
+
+            let description = format!("Credit rule {} ({})", rule.name, period);
+            let expires_at = rule
+                .expires_in_days
+                .map(|days| Utc::now().naive_utc() + TimeDelta::days(i64::from(days)));
+            let wallet =
+                credit_wallet(conn, user_uuid, rule.amount, &description, expires_at).await?;
+            Ok::<Option<Wallet>, AppError>(Some(wallet))
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn at(y: i32, m: u32, d: u32) -> NaiveDateTime {
+        date(y, m, d).and_hms_opt(12, 0, 0).unwrap()
+    }
+
+    fn rule(kind: CreditRuleKind, created_at: NaiveDateTime) -> CreditRule {
+        CreditRule {
+            uuid: Uuid::nil(),
+            name: "Test".to_string(),
+            kind,
+            amount: 10,
+            day_of_month: Some(15),
+            run_at: None,
+            school: None,
+            bunk: None,
+            paused_at: None,
+            created_by: Uuid::nil(),
+            created_at,
+            updated_at: created_at,
+            expires_in_days: None,
+        }
+    }
+
+    #[test]
+    fn parses_every_dob_format() {
+        let expected = Some(date(2010, 3, 7));
+        assert_eq!(parse_dob("07032010"), expected);
+        assert_eq!(parse_dob("2010-03-07"), expected);
+        assert_eq!(parse_dob("07/03/2010"), expected);
+        assert_eq!(parse_dob("07-03-2010"), expected);
+        assert_eq!(parse_dob(" 2010-03-07 "), expected);
+        assert_eq!(parse_dob("March 7"), None);
+        assert_eq!(parse_dob("31022010"), None);
+    }
+
+    #[test]
+    fn leap_day_birthdays_fall_on_the_28th_in_other_years() {
+        let dob = date(2012, 2, 29);
+        assert_eq!(birthday_in(dob, 2025), Some(date(2025, 2, 28)));
+        assert_eq!(birthday_in(dob, 2028), Some(date(2028, 2, 29)));
+        assert_eq!(birthday_in(date(2010, 3, 7), 2025), Some(date(2025, 3, 7)));
+    }
+
+    #[test]
+    fn birthdays_are_paid_within_the_grace_period() {
+        let dob = date(2010, 3, 7);
+        let since = date(2025, 1, 1);
+        assert!(!birthday_due(dob, date(2025, 3, 6), since));
+        assert!(birthday_due(dob, date(2025, 3, 7), since));
+        assert!(birthday_due(dob, date(2025, 3, 14), since));
+        assert!(!birthday_due(dob, date(2025, 3, 15), since));
+        // A resident added in December is not paid for a March birthday
+        assert!(!birthday_due(dob, date(2025, 12, 1), since));
+    }
+
+    #[test]
+    fn birthdays_before_the_rule_existed_are_not_paid() {
+        let dob = date(2010, 3, 7);
+        assert!(!birthday_due(dob, date(2025, 3, 8), date(2025, 3, 8)));
+        assert!(birthday_due(dob, date(2025, 3, 8), date(2025, 3, 7)));
+    }
+
+    #[test]
+    fn leap_day_birthdays_are_paid_in_other_years() {
+        let dob = date(2012, 2, 29);
+        let since = date(2025, 1, 1);
+        assert!(birthday_due(dob, date(2025, 2, 28), since));
+        assert!(birthday_due(dob, date(2025, 3, 1), since));
+    }
+
+    #[test]
+    fn monthly_rules_are_due_from_the_day_of_month() {
+        let monthly = rule(CreditRuleKind::Monthly, at(2025, 1, 1));
+        assert!(due_periods(&monthly, at(2025, 1, 14), None).is_empty());
+        assert_eq!(due_periods(&monthly, at(2025, 1, 15), None), ["2025-01"]);
+        assert_eq!(
+            due_periods(&monthly, at(2025, 1, 31), Some("2025-01")),
+            ["2025-01"]
+        );
+    }
+
+    #[test]
+    fn monthly_rules_skip_months_before_they_were_created() {
+        let monthly = rule(CreditRuleKind::Monthly, at(2025, 1, 20));
+        assert!(due_periods(&monthly, at(2025, 1, 31), None).is_empty());
+        assert_eq!(due_periods(&monthly, at(2025, 2, 15), None), ["2025-02"]);
+    }
+
+    #[test]
+    fn monthly_rules_catch_up_on_missed_months() {
+        let monthly = rule(CreditRuleKind::Monthly, at(2024, 6, 1));
+        assert_eq!(
+            due_periods(&monthly, at(2025, 3, 20), Some("2025-01")),
+            ["2025-01", "2025-02", "2025-03"]
+        );
+        assert_eq!(
+            due_periods(&monthly, at(2025, 1, 10), Some("2024-11")),
+            ["2024-11", "2024-12"]
+        );
+    }
+
+    #[test]
+    fn monthly_rules_that_never_paid_catch_up_from_creation() {
+        let monthly = rule(CreditRuleKind::Monthly, at(2025, 1, 10));
+        assert_eq!(
+            due_periods(&monthly, at(2025, 3, 16), None),
+            ["2025-01", "2025-02", "2025-03"]
+        );
+    }
+
+    #[test]
+    fn once_rules_are_due_within_the_grace_period() {
+        let mut once = rule(CreditRuleKind::Once, at(2025, 1, 1));
+        once.run_at = Some(at(2025, 2, 1));
+        assert!(due_periods(&once, at(2025, 1, 31), None).is_empty());
+        assert_eq!(due_periods(&once, at(2025, 2, 1), None), ["once"]);
+        assert_eq!(due_periods(&once, at(2025, 2, 7), None), ["once"]);
+        assert!(due_periods(&once, at(2025, 2, 8), None).is_empty());
+    }
+
+    #[test]
+    fn birthday_rules_pay_for_the_current_year() {
+        let birthday = rule(CreditRuleKind::Birthday, at(2025, 1, 1));
+        assert_eq!(due_periods(&birthday, at(2025, 6, 1), None), ["2025"]);
+    }
+}
@@ -0,0 +1,13 @@
+use serde::de::DeserializeOwned;
+
+/// Parses every data row of an uploaded CSV, keeping the line number for error reports.
+pub fn read_rows<T: DeserializeOwned>(data: &[u8]) -> Vec<(usize, Result<T, String>)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data);
+    reader
+        .deserialize::<T>()
+        .enumerate()
+        .map(|(i, row)| (i + 2, row.map_err(|e| e.to_string())))
+        .collect()
+}
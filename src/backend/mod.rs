@@ -1 +1,12 @@
+pub mod auction;
+pub mod audit;
+pub mod catalogue;
+pub mod credit_rules;
+pub mod csv_import;
+pub mod policy;
+pub mod product_csv;
 pub mod pw_reset;
+pub mod rate_limit;
+pub mod sessions;
+pub mod stock;
+pub mod wallet;
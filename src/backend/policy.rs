@@ -0,0 +1,296 @@
+use crate::models::policy::{NewPolicyRule, PolicyRule};
+use crate::schema::private;
+use async_trait::async_trait;
+use axum_casbin::casbin::error::AdapterError;
+use axum_casbin::casbin::function_map::key_match2;
+use axum_casbin::casbin::{
+    Adapter, CachedEnforcer, CoreApi, DefaultModel, Filter, Model, Result as CasbinResult,
+};
+use diesel::prelude::*;
+use diesel_async::pooled_connection::bb8::{Pool, PooledConnection};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+
+/// Casbin adapter that keeps `p` and `g*` rules in `private.casbin_rules`.
+pub struct PgPolicyAdapter {
+    pool: Pool<AsyncPgConnection>,
+    is_filtered: bool,
+}
+
+impl PgPolicyAdapter {
+    pub fn new(pool: Pool<AsyncPgConnection>) -> Self {
+        PgPolicyAdapter {
+            pool,
+            is_filtered: false,
+        }
+    }
+
+    async fn conn(&self) -> CasbinResult<PooledConnection<'_, AsyncPgConnection>> {
+        self.pool.get().await.map_err(adapter_error)
+    }
+
+    async fn load_rules(&self) -> CasbinResult<Vec<PolicyRule>> {
+        let mut conn = self.conn().await?;
+        private::casbin_rules::table
+            .select(PolicyRule::as_select())
+            .order(private::casbin_rules::id.asc())
+            .load::<PolicyRule>(&mut conn)
+            .await
+            .map_err(adapter_error)
+    }
+}
+
+/// Builds the enforcer used by the Casbin layer, loading policies from the database.
+pub async fn build_enforcer(pool: Pool<AsyncPgConnection>) -> CasbinResult<CachedEnforcer> {
+    let model = DefaultModel::from_file("casbin.conf").await?;
+    let enforcer = CachedEnforcer::new(model, PgPolicyAdapter::new(pool)).await?;
+    enforcer
+        .get_role_manager()
+        .write()
+        .matching_fn(Some(key_match2), None);
+    Ok(enforcer)
+}
+
+fn adapter_error<E: std::error::Error + Send + Sync + 'static>(
+    err: E,
+) -> axum_casbin::casbin::Error {
+    AdapterError(Box::new(err)).into()
+}
+
+fn section(ptype: &str) -> String {
+    ptype.chars().take(1).collect()
+}
+
+fn load_rule(m: &mut dyn Model, ptype: &str, rule: Vec<String>) {
+    if let Some(ast) = m
+        .get_mut_model()
+        .get_mut(&section(ptype))
+        .and_then(|ast_map| ast_map.get_mut(ptype))
+    {
+        ast.get_mut_policy().insert(rule);
+    }
+}
+
+fn matches_filter(filter: &[&str], rule: &[String]) -> bool {
+    filter
+        .iter()
+        .zip(rule.iter())
+        .all(|(expected, value)| expected.is_empty() || expected == value)
+}
+
+/// Inserts a single rule, returning `false` if it already exists.
+pub async fn insert_rule(
+    conn: &mut AsyncPgConnection,
+    ptype: &str,
+    rule: Vec<String>,
+) -> Result<bool, diesel::result::Error> {
+    let inserted = diesel::insert_into(private::casbin_rules::table)
+        .values(NewPolicyRule::new(ptype, rule))
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .await?;
+    Ok(inserted == 1)
+}
+
+pub async fn delete_rule(
+    conn: &mut AsyncPgConnection,
+    ptype: &str,
+    rule: Vec<String>,
+) -> Result<usize, diesel::result::Error> {
+    let rule = NewPolicyRule::new(ptype, rule);
+    diesel::delete(
+        private::casbin_rules::table
+            .filter(private::casbin_rules::ptype.eq(rule.ptype))
+            .filter(private::casbin_rules::v0.eq(rule.v0))
+            .filter(private::casbin_rules::v1.eq(rule.v1))
+            .filter(private::casbin_rules::v2.eq(rule.v2))
+            .filter(private::casbin_rules::v3.eq(rule.v3))
+            .filter(private::casbin_rules::v4.eq(rule.v4))
+            .filter(private::casbin_rules::v5.eq(rule.v5)),
+    )
+    .execute(conn)
+    .await
+}
+
+#[async_trait]
+impl Adapter for PgPolicyAdapter {
+    async fn load_policy(&mut self, m: &mut dyn Model) -> CasbinResult<()> {
+        self.is_filtered = false;
+        for rule in self.load_rules().await? {
+            load_rule(m, &rule.ptype, rule.values());
+        }
+        Ok(())
+    }
+
+    async fn load_filtered_policy<'a>(
+        &mut self,
+        m: &mut dyn Model,
+        f: Filter<'a>,
+    ) -> CasbinResult<()> {
+        self.is_filtered = false;
+        for rule in self.load_rules().await? {
+            let values = rule.values();
+            let filter = if section(&rule.ptype) == "p" {
+                &f.p
+            } else {
+                &f.g
+            };
+            if matches_filter(filter, &values) {
+                load_rule(m, &rule.ptype, values);
+            } else {
+                self.is_filtered = true;
+            }
+        }
+        Ok(())
+    }
+
+    async fn save_policy(&mut self, m: &mut dyn Model) -> CasbinResult<()> {
+        let mut rules = vec![];
+        for sec in ["p", "g"] {
+            if let Some(ast_map) = m.get_model().get(sec) {
+                for (ptype, ast) in ast_map {
+                    rules.extend(
+                        ast.get_policy()
+                            .iter()
+                            .map(|rule| NewPolicyRule::new(ptype, rule.clone())),
+                    );
+                }
+            }
+        }
+
+        let mut conn = self.conn().await?;
+        conn.transaction(|conn| {
+            async move {
+                diesel::delete(private::casbin_rules::table)
+                    .execute(conn)
+                    .await?;
+                diesel::insert_into(private::casbin_rules::table)
+                    .values(&rules)
+                    .execute(conn)
+                    .await?;
+                Ok::<(), diesel::result::Error>(())
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(adapter_error)
+    }
+
+    async fn clear_policy(&mut self) -> CasbinResult<()> {
+        self.is_filtered = false;
+        let mut conn = self.conn().await?;
+        diesel::delete(private::casbin_rules::table)
+            .execute(&mut conn)
+            .await
+            .map_err(adapter_error)?;
+        Ok(())
+    }
+
+    fn is_filtered(&self) -> bool {
+        self.is_filtered
+    }
+
+    async fn add_policy(
+        &mut self,
+        _sec: &str,
+        ptype: &str,
+        rule: Vec<String>,
+    ) -> CasbinResult<bool> {
+        let mut conn = self.conn().await?;
+        insert_rule(&mut conn, ptype, rule)
+            .await
+            .map_err(adapter_error)
+    }
+
+    async fn add_policies(
+        &mut self,
+        _sec: &str,
+        ptype: &str,
+        rules: Vec<Vec<String>>,
+    ) -> CasbinResult<bool> {
+        let rules = rules
+            .into_iter()
+            .map(|rule| NewPolicyRule::new(ptype, rule))
+            .collect::<Vec<NewPolicyRule>>();
+        let mut conn = self.conn().await?;
+        diesel::insert_into(private::casbin_rules::table)
+            .values(&rules)
+            .execute(&mut conn)
+            .await
+            .map_err(adapter_error)?;
+        Ok(true)
+    }
+
+    async fn remove_policy(
+        &mut self,
+        _sec: &str,
+        ptype: &str,
+        rule: Vec<String>,
+    ) -> CasbinResult<bool> {
+        let mut conn = self.conn().await?;
+        let deleted = delete_rule(&mut conn, ptype, rule)
+            .await
+            .map_err(adapter_error)?;
+        Ok(deleted > 0)
+    }
+
+    async fn remove_policies(
+        &mut self,
+        _sec: &str,
+        ptype: &str,
+        rules: Vec<Vec<String>>,
+    ) -> CasbinResult<bool> {
+        let mut conn = self.conn().await?;
+        let ptype = ptype.to_string();
+        conn.transaction(|conn| {
+            async move {
+                for rule in rules {
+                    if delete_rule(conn, &ptype, rule).await? == 0 {
+                        return Err(diesel::result::Error::RollbackTransaction);
+                    }
+                }
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await
+        .map(|_| true)
+        .or_else(|err| match err {
+            diesel::result::Error::RollbackTransaction => Ok(false),
+            err => Err(adapter_error(err)),
+        })
+    }
+
+    async fn remove_filtered_policy(
+        &mut self,
+        _sec: &str,
+        ptype: &str,
+        field_index: usize,
+        field_values: Vec<String>,
+    ) -> CasbinResult<bool> {
+        if field_values.is_empty() || field_index + field_values.len() > 6 {
+            return Ok(false);
+        }
+
+        let mut query = diesel::delete(private::casbin_rules::table)
+            .filter(private::casbin_rules::ptype.eq(ptype.to_string()))
+            .into_boxed();
+        for (offset, value) in field_values.into_iter().enumerate() {
+            if value.is_empty() {
+                continue;
+            }
+            query = match field_index + offset {
+                0 => query.filter(private::casbin_rules::v0.eq(value)),
+                1 => query.filter(private::casbin_rules::v1.eq(value)),
+                2 => query.filter(private::casbin_rules::v2.eq(value)),
+                3 => query.filter(private::casbin_rules::v3.eq(value)),
+                4 => query.filter(private::casbin_rules::v4.eq(value)),
+                _ => query.filter(private::casbin_rules::v5.eq(value)),
+            };
+        }
+
+        let mut conn = self.conn().await?;
+        let deleted = query.execute(&mut conn).await.map_err(adapter_error)?;
+        Ok(deleted > 0)
+    }
+}
@@ -0,0 +1,165 @@
+use crate::backend::catalogue::load_tags;
+use crate::models::products::ProductRecord;
+use crate::req_res::inventory::ProductCsvRow;
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use crate::schema::private;
+use diesel::prelude::*;
+use diesel_async::pooled_connection::bb8::Pool;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use log::{error, warn};
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use uuid::Uuid;
+
+const EXPORT_PAGE_SIZE: i64 = 500;
+const TAG_SEPARATOR: char = ';';
+/// Most bytes an image archive may inflate to, guards against zip bombs
+const MAX_ARCHIVE_UNPACKED_SIZE: u64 = 500 * 1024 * 1024;
+
+/// Extracts the files of an image archive keyed by file name, ignoring folders.
+/// Archives that unpack to more than `MAX_ARCHIVE_UNPACKED_SIZE` are rejected.
+pub fn read_images(data: &[u8]) -> Result<HashMap<String, Vec<u8>>, AppError> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).map_err(|e| {
+        warn!("Unreadable image archive: {}", e);
+        AppError::bad_request(None)
+    })?;
+
+    let mut images = HashMap::new();
+    let mut remaining = MAX_ARCHIVE_UNPACKED_SIZE;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|e| {
+            warn!("Unreadable entry in image archive: {}", e);
+            AppError::bad_request(None)
+        })?;
+        if !file.is_file() {
+            continue;
+        }
+        let Some(name) = Path::new(file.name())
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+        else {
+            continue;
+        };
+        // The declared size can be forged, so the read itself is capped as well
+        if file.size() > remaining {
+            return Err(archive_too_large());
+        }
+        let mut content = vec![];
+        file.by_ref()
+            .take(remaining + 1)
+            .read_to_end(&mut content)
+            .map_err(|e| {
+                warn!("Unreadable entry {} in image archive: {}", name, e);
+                AppError::bad_request(None)
+            })?;
+        if content.len() as u64 > remaining {
+            return Err(archive_too_large());
+        }
+        remaining -= content.len() as u64;
+        images.insert(name, content);
+    }
+    Ok(images)
+}
+
+fn archive_too_large() -> AppError {
+    let errors = vec![format!(
+        "Image archive must not unpack to more than {} MiB",
+        MAX_ARCHIVE_UNPACKED_SIZE / 1024 / 1024
+    )];
+    AppError::bad_request::<ClientErrorMessages>(DataValidationError { errors }.into())
+}
+
+pub fn split_tags(tags: Option<String>) -> Vec<String> {
+    tags.map(|tags| {
+        tags.split(TAG_SEPARATOR)
+            .filter(|tag| !tag.trim().is_empty())
+            .map(str::to_string)
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Writes the active catalogue as CSV a page at a time, so the export never holds
+/// the whole catalogue in memory. Stops early if the client goes away.
+pub async fn write_catalogue<W: AsyncWrite + Unpin>(pool: Pool<AsyncPgConnection>, mut out: W) {
+    if let Err(e) = write_pages(&pool, &mut out).await {
+        error!("Catalogue export failed: {:?}", e);
+    }
+    let _ = out.shutdown().await;
+}
+
+async fn write_pages<W: AsyncWrite + Unpin>(
+    pool: &Pool<AsyncPgConnection>,
+    out: &mut W,
+) -> Result<(), AppError> {
+    let mut con = pool.get().await?;
+    let categories = private::categories::table
+        .select((private::categories::id, private::categories::name))
+        .load::<(i32, String)>(&mut con)
+        .await?
+        .into_iter()
+        .collect::<HashMap<i32, String>>();
+
+    let mut after: Option<Uuid> = None;
+    let mut has_headers = true;
+    loop {
+        let mut query = private::products::table
+            .filter(private::products::archived_at.is_null())
+            .into_boxed();
+        if let Some(last) = after {
+            query = query.filter(private::products::uuid.gt(last));
+        }
+        let records = query
+            .select(ProductRecord::as_select())
+            .order(private::products::uuid.asc())
+            .limit(EXPORT_PAGE_SIZE)
+            .load::<ProductRecord>(&mut con)
+            .await?;
+        let Some(last) = records.last() else {
+            return Ok(());
+        };
+        after = Some(last.uuid);
+
+        let uids = records
+            .iter()
+            .map(|record| record.uuid)
+            .collect::<Vec<Uuid>>();
+        let mut tags = load_tags(&mut con, &uids).await?;
+
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(has_headers)
+            .from_writer(vec![]);
+        has_headers = false;
+        for record in records {
+            let row = ProductCsvRow {
+                uuid: Some(record.uuid),
+                category: record
+                    .category_id
+                    .and_then(|id| categories.get(&id).cloned()),
+                tags: tags
+                    .remove(&record.uuid)
+                    .map(|tags| tags.join(&TAG_SEPARATOR.to_string())),
+                title: record.title,
+                description: record.description,
+                stock: record.stock,
+                cost: record.cost,
+                reorder_threshold: Some(record.reorder_threshold),
+                image: Some(record.image_path).filter(|path| !path.is_empty()),
+            };
+            writer.serialize(row).map_err(csv_error)?;
+        }
+        let page = writer.into_inner().map_err(|e| csv_error(e.into_error()))?;
+
+        if out.write_all(&page).await.is_err() {
+            warn!("Catalogue export cancelled by the client");
+            return Ok(());
+        }
+    }
+}
+
+fn csv_error<E: std::fmt::Display>(e: E) -> AppError {
+    error!("CSV: {}", e);
+    AppError::internal_error("CSV error".to_string())
+}
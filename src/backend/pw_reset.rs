@@ -26,9 +26,17 @@ pub struct PasswordResetResult {
 pub enum ResetStatus {
     Valid,
     OtpInvalid,
+    AttemptsExceeded,
     NotFound,
 }
 
+const SESSION_TTL_SECS: i64 = 660;
+const MAX_OTP_ATTEMPTS: i64 = 5;
+
+fn attempts_key(session_uid: Uuid) -> String {
+    format!("{}:attempts", session_uid)
+}
+
 pub async fn new_password_reset_req(
     redis: &Client,
     session_uid: Uuid,
@@ -43,9 +51,9 @@ pub async fn new_password_reset_req(
         reset_token: generate_reset_token(),
     };
     let packed = serialize_to_messagepack(&reset_req);
-    let exp = Expiration::EX(660);
+    let exp = Expiration::EX(SESSION_TTL_SECS);
     redis
-        .set(
+        .set::<(), _, _>(
             session_uid.to_string(),
             packed.as_slice(),
             Some(exp),
@@ -69,11 +77,30 @@ pub async fn verify_password_reset_otp(
                 AppError::internal_error("unknown msg pack deserialization failure".to_string())
             })?;
 
+            // Count every guess up front so concurrent requests cannot exceed the limit
+            let attempts: i64 = redis.incr(attempts_key(session_uid)).await?;
+            redis
+                .expire::<(), _>(attempts_key(session_uid), SESSION_TTL_SECS, None)
+                .await?;
+            if attempts > MAX_OTP_ATTEMPTS {
+                invalidate_session(redis, session_uid).await?;
+                return Ok(PasswordResetResult {
+                    status: ResetStatus::AttemptsExceeded,
+                    reset_token: None,
+                });
+            }
+
             if reset_req.otp == otp {
                 Ok(PasswordResetResult {
                     status: ResetStatus::Valid,
                     reset_token: Some(reset_req.reset_token),
                 })
+            } else if attempts >= MAX_OTP_ATTEMPTS {
+                invalidate_session(redis, session_uid).await?;
+                Ok(PasswordResetResult {
+                    status: ResetStatus::AttemptsExceeded,
+                    reset_token: None,
+                })
             } else {
                 Ok(PasswordResetResult {
                     status: ResetStatus::OtpInvalid,
@@ -112,6 +139,13 @@ pub async fn verify_reset_token(
     }
 }
 
+async fn invalidate_session(redis: &Client, session_uid: Uuid) -> Result<(), AppError> {
+    redis
+        .del::<(), _>(vec![session_uid.to_string(), attempts_key(session_uid)])
+        .await?;
+    Ok(())
+}
+
 fn generate_reset_token() -> String {
     let mut rng = thread_rng();
     let mut bytes = [0u8; 32];
@@ -0,0 +1,140 @@
+use crate::req_res::AppError;
+use chrono::Utc;
+use fred::prelude::*;
+use log::warn;
+use uuid::Uuid;
+
+pub struct RateLimit {
+    pub scope: &'static str,
+    pub max_requests: i64,
+    pub window_secs: i64,
+}
+
+pub const LOGIN_PER_IP: RateLimit = RateLimit {
+    scope: "login_ip",
+    max_requests: 30,
+    window_secs: 300,
+};
+pub const LOGIN_PER_ACCOUNT: RateLimit = RateLimit {
+    scope: "login_account",
+    max_requests: 10,
+    window_secs: 300,
+};
+pub const PW_RESET_PER_IP: RateLimit = RateLimit {
+    scope: "pw_reset_ip",
+    max_requests: 10,
+    window_secs: 900,
+};
+pub const PW_RESET_PER_PHONE: RateLimit = RateLimit {
+    scope: "pw_reset_phone",
+    max_requests: 3,
+    window_secs: 900,
+};
+pub const OTP_PER_IP: RateLimit = RateLimit {
+    scope: "otp_ip",
+    max_requests: 20,
+    window_secs: 900,
+};
+pub const OTP_PER_SESSION: RateLimit = RateLimit {
+    scope: "otp_session",
+    max_requests: 10,
+    window_secs: 600,
+};
+
+const MAX_LOGIN_FAILURES: i64 = 5;
+const LOGIN_LOCKOUT_SECS: i64 = 900;
+
+/// Trims the window, counts it and records the request in one step, so concurrent
+/// requests cannot all read the same count. Returns the oldest timestamp in a full
+/// window, or nil if the request was let through.
+const RATE_LIMIT_SCRIPT: &str = r#"
+redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', ARGV[1] - ARGV[2])
+if redis.call('ZCARD', KEYS[1]) >= tonumber(ARGV[3]) then
+    local oldest = redis.call('ZRANGE', KEYS[1], 0, 0, 'WITHSCORES')
+    return tonumber(oldest[2])
+end
+redis.call('ZADD', KEYS[1], ARGV[1], ARGV[4])
+redis.call('PEXPIRE', KEYS[1], ARGV[2])
+return false
+"#;
+
+/// Sliding window limiter backed by a sorted set of request timestamps.
+/// Rejected requests are not counted towards the window.
+pub async fn check_rate_limit(
+    redis: &Client,
+    limit: &RateLimit,
+    identifier: &str,
+) -> Result<(), AppError> {
+    let key = format!("ratelimit:{}:{}", limit.scope, identifier);
+    let now = Utc::now().timestamp_millis();
+    let window_ms = limit.window_secs * 1000;
+    // Members must be unique, so pair the timestamp with a random suffix
+    let member = format!("{}:{}", now, Uuid::new_v4());
+
+    let oldest: Option<i64> = redis
+        .eval(
+            RATE_LIMIT_SCRIPT,
+            key,
+            vec![
+                now.to_string(),
+                window_ms.to_string(),
+                limit.max_requests.to_string(),
+                member,
+            ],
+        )
+        .await?;
+    if let Some(oldest_ms) = oldest {
+        let retry_after = (oldest_ms + window_ms - now) / 1000 + 1;
+        warn!("Rate limit {} hit for {}", limit.scope, identifier);
+        return Err(AppError::too_many_requests(retry_after.max(1) as u64));
+    }
+    Ok(())
+}
+
+fn login_failures_key(resident_id: &str) -> String {
+    format!("login_failures:{}", resident_id)
+}
+
+fn login_lockout_key(resident_id: &str) -> String {
+    format!("login_lockout:{}", resident_id)
+}
+
+/// Rejects the login attempt with the remaining lockout time if the account is locked.
+pub async fn check_login_lockout(redis: &Client, resident_id: &str) -> Result<(), AppError> {
+    let ttl: i64 = redis.ttl(login_lockout_key(resident_id)).await?;
+    if ttl > 0 {
+        return Err(AppError::too_many_requests(ttl as u64));
+    }
+    Ok(())
+}
+
+/// Counts a failed login and locks the account once `MAX_LOGIN_FAILURES` is reached.
+pub async fn record_login_failure(redis: &Client, resident_id: &str) -> Result<(), AppError> {
+    let key = login_failures_key(resident_id);
+    let failures: i64 = redis.incr(&key).await?;
+    redis
+        .expire::<(), _>(&key, LOGIN_LOCKOUT_SECS, None)
+        .await?;
+    if failures >= MAX_LOGIN_FAILURES {
+        warn!(
+            "Locking {} for {}s after {} failed logins",
+            resident_id, LOGIN_LOCKOUT_SECS, failures
+        );
+        redis
+            .set::<(), _, _>(
+                login_lockout_key(resident_id),
+                1,
+                Some(Expiration::EX(LOGIN_LOCKOUT_SECS)),
+                None,
+                false,
+            )
+            .await?;
+        redis.del::<(), _>(&key).await?;
+    }
+    Ok(())
+}
+
+pub async fn clear_login_failures(redis: &Client, resident_id: &str) -> Result<(), AppError> {
+    redis.del::<(), _>(login_failures_key(resident_id)).await?;
+    Ok(())
+}
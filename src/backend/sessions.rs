@@ -0,0 +1,149 @@
+use crate::paseto::REFRESH_TOKEN_LIFETIME_DAYS;
+use crate::req_res::AppError;
+use crate::utils::{deserialize_from_messagepack, serialize_to_messagepack};
+use fred::prelude::*;
+use fred::types::SetOptions;
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const SESSION_TTL_SECS: i64 = REFRESH_TOKEN_LIFETIME_DAYS * 24 * 60 * 60;
+
+/// Every login starts a token family; each refresh token issued within it
+/// is tracked by its jti so that replaying a rotated token can be detected.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RefreshSession {
+    pub user_uuid: Uuid,
+    pub family_id: Uuid,
+}
+
+fn refresh_key(jti: Uuid) -> String {
+    format!("refresh:{}", jti)
+}
+
+fn refresh_used_key(jti: Uuid) -> String {
+    format!("refresh_used:{}", jti)
+}
+
+fn family_key(family_id: Uuid) -> String {
+    format!("refresh_family:{}", family_id)
+}
+
+fn user_sessions_key(user_uuid: Uuid) -> String {
+    format!("user_sessions:{}", user_uuid)
+}
+
+/// Starts a new token family for the user and returns the jti of its first refresh token.
+pub async fn start_session(redis: &Client, user_uuid: Uuid) -> Result<Uuid, AppError> {
+    let family_id = Uuid::new_v4();
+    redis
+        .set::<(), _, _>(
+            family_key(family_id),
+            user_uuid.to_string(),
+            Some(Expiration::EX(SESSION_TTL_SECS)),
+            None,
+            false,
+        )
+        .await?;
+    redis
+        .sadd::<(), _, _>(user_sessions_key(user_uuid), family_id.to_string())
+        .await?;
+    redis
+        .expire::<(), _>(user_sessions_key(user_uuid), SESSION_TTL_SECS, None)
+        .await?;
+
+    issue_refresh_jti(
+        redis,
+        RefreshSession {
+            user_uuid,
+            family_id,
+        },
+    )
+    .await
+}
+
+/// Consumes the refresh token identified by `jti` and returns the session it belongs to
+/// along with the jti for its replacement. Presenting an already rotated token revokes the
+/// whole family, since it means the token has been stolen or replayed.
+pub async fn rotate_session(redis: &Client, jti: Uuid) -> Result<(RefreshSession, Uuid), AppError> {
+    let bytes: Option<Vec<u8>> = redis.get(refresh_key(jti)).await?;
+    let session: RefreshSession = match bytes {
+        Some(data) => deserialize_from_messagepack(&data).map_err(|e| {
+            error!("msgpack deserialization failure: {}", e.to_string());
+            AppError::internal_error("unknown msg pack deserialization failure".to_string())
+        })?,
+        None => return Err(AppError::unauthorized()),
+    };
+
+    let family_active: i64 = redis.exists(family_key(session.family_id)).await?;
+    if family_active == 0 {
+        return Err(AppError::unauthorized());
+    }
+
+    let first_use: Option<String> = redis
+        .set(
+            refresh_used_key(jti),
+            1,
+            Some(Expiration::EX(SESSION_TTL_SECS)),
+            Some(SetOptions::NX),
+            false,
+        )
+        .await?;
+    if first_use.is_none() {
+        warn!(
+            "Refresh token reuse detected for user {}, revoking family {}",
+            session.user_uuid, session.family_id
+        );
+        revoke_family(redis, &session).await?;
+        return Err(AppError::unauthorized());
+    }
+
+    redis
+        .expire::<(), _>(family_key(session.family_id), SESSION_TTL_SECS, None)
+        .await?;
+    redis
+        .expire::<(), _>(user_sessions_key(session.user_uuid), SESSION_TTL_SECS, None)
+        .await?;
+    let next_jti = issue_refresh_jti(redis, session.clone()).await?;
+    Ok((session, next_jti))
+}
+
+/// Revokes every token family belonging to the user, logging them out on all devices
+/// once their current access token expires.
+pub async fn revoke_user_sessions(redis: &Client, user_uuid: Uuid) -> Result<(), AppError> {
+    let families: Vec<String> = redis.smembers(user_sessions_key(user_uuid)).await?;
+    let mut keys = families
+        .iter()
+        .filter_map(|family| Uuid::parse_str(family).ok())
+        .map(family_key)
+        .collect::<Vec<String>>();
+    keys.push(user_sessions_key(user_uuid));
+    redis.del::<(), _>(keys).await?;
+    Ok(())
+}
+
+async fn revoke_family(redis: &Client, session: &RefreshSession) -> Result<(), AppError> {
+    redis.del::<(), _>(family_key(session.family_id)).await?;
+    redis
+        .srem::<(), _, _>(
+            user_sessions_key(session.user_uuid),
+            session.family_id.to_string(),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn issue_refresh_jti(redis: &Client, session: RefreshSession) -> Result<Uuid, AppError> {
+    let jti = Uuid::new_v4();
+    let packed = serialize_to_messagepack(&session);
+    redis
+        .set::<(), _, _>(
+            refresh_key(jti),
+            packed.as_slice(),
+            Some(Expiration::EX(SESSION_TTL_SECS)),
+            None,
+            false,
+        )
+        .await?;
+    Ok(jti)
+}
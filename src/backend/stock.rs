@@ -0,0 +1,147 @@
+use crate::models::products::LowStockProduct;
+use crate::models::stock::NewStockMovement;
+use crate::models::user::AccountType;
+use crate::notifications::queue::enqueue;
+use crate::notifications::templates::Template;
+use crate::notifications::Recipient;
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use crate::schema::private;
+use crate::websocket::emit_low_stock;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_async::pooled_connection::bb8::Pool;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use fred::clients::Client;
+use log::{error, info};
+use socketioxide::SocketIo;
+use std::time::Duration;
+
+const LOW_STOCK_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Applies the movement to the product's stock and adds it to the ledger,
+/// rejecting changes that would leave stock negative. Returns the new stock.
+/// Callers are expected to run this inside a database transaction.
+pub async fn record_movement(
+    conn: &mut AsyncPgConnection,
+    movement: NewStockMovement,
+) -> Result<i32, AppError> {
+    let (title, stock) = private::products::table
+        .find(movement.product_uuid)
+        .select((private::products::title, private::products::stock))
+        .for_update()
+        .first::<(String, i32)>(conn)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)?;
+
+    if stock
+        .checked_add(movement.delta)
+        .is_none_or(|stock| stock < 0)
+    {
+        let errors = vec![format!("Insufficient stock for {}", title)];
+        return Err(AppError::bad_request::<ClientErrorMessages>(
+            DataValidationError { errors }.into(),
+        ));
+    }
+
+    let stock = diesel::update(private::products::table.find(movement.product_uuid))
+        .set(private::products::stock.eq(private::products::stock + movement.delta))
+        .returning(private::products::stock)
+        .get_result::<i32>(conn)
+        .await?;
+
+    diesel::insert_into(private::stock_movements::table)
+        .values(&movement)
+        .execute(conn)
+        .await?;
+
+    Ok(stock)
+}
+
+/// Active products at or below their reorder threshold, emptiest first.
+pub async fn find_low_stock(
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<LowStockProduct>, AppError> {
+    let products = private::products::table
+        .filter(private::products::archived_at.is_null())
+        .filter(private::products::stock.le(private::products::reorder_threshold))
+        .select(LowStockProduct::as_select())
+        .order((
+            (private::products::stock - private::products::reorder_threshold).asc(),
+            private::products::title.asc(),
+        ))
+        .load::<LowStockProduct>(conn)
+        .await?;
+    Ok(products)
+}
+
+/// Periodically alerts staff about products that fell to their reorder threshold
+/// since the last check. Runs until the process exits.
+pub async fn run_low_stock_monitor(pool: Pool<AsyncPgConnection>, redis: Client, io: SocketIo) {
+    let mut interval = tokio::time::interval(LOW_STOCK_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = check_low_stock(&pool, &redis, &io).await {
+            error!("Low stock check failed: {:?}", e);
+        }
+    }
+}
+
+async fn check_low_stock(
+    pool: &Pool<AsyncPgConnection>,
+    redis: &Client,
+    io: &SocketIo,
+) -> Result<(), AppError> {
+    let mut con = pool.get().await?;
+    // Products back above their threshold are alerted again the next time they run low
+    diesel::update(private::products::table)
+        .filter(private::products::low_stock_alerted_at.is_not_null())
+        .filter(private::products::stock.gt(private::products::reorder_threshold))
+        .set(private::products::low_stock_alerted_at.eq(None::<NaiveDateTime>))
+        .execute(&mut con)
+        .await?;
+
+    let crossed = private::products::table
+        .filter(private::products::archived_at.is_null())
+        .filter(private::products::low_stock_alerted_at.is_null())
+        .filter(private::products::stock.le(private::products::reorder_threshold))
+        .select(LowStockProduct::as_select())
+        .load::<LowStockProduct>(&mut con)
+        .await?;
+    if crossed.is_empty() {
+        return Ok(());
+    }
+
+    let admins = private::users::table
+        .filter(private::users::role.eq(AccountType::Admin))
+        .filter(private::users::active.eq(true))
+        .select(private::users::email)
+        .load::<String>(&mut con)
+        .await?;
+    let digest = Template::LowStockDigest { products: &crossed };
+    let mut queued = 0;
+    for email in &admins {
+        match enqueue(redis, digest.render(Recipient::Email(email.clone()))).await {
+            Ok(()) => queued += 1,
+            Err(e) => error!("Unable to queue low stock digest for {}: {:?}", email, e),
+        }
+    }
+    // Only mark the products once someone was told, otherwise the next check retries
+    if queued == 0 && !admins.is_empty() {
+        return Ok(());
+    }
+
+    let uuids = crossed
+        .iter()
+        .map(|product| product.uuid)
+        .collect::<Vec<_>>();
+    diesel::update(private::products::table)
+        .filter(private::products::uuid.eq_any(&uuids))
+        .filter(private::products::low_stock_alerted_at.is_null())
+        .set(private::products::low_stock_alerted_at.eq(diesel::dsl::now))
+        .execute(&mut con)
+        .await?;
+    info!("{} products reached their reorder threshold", crossed.len());
+    emit_low_stock(io, &crossed);
+    Ok(())
+}
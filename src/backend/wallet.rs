@@ -0,0 +1,352 @@
+use crate::models::wallet::{CreditLot, TransactionType, Wallet};
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use crate::schema::private;
+use crate::websocket::emit_balance_changed;
+use chrono::{NaiveDateTime, NaiveTime, TimeDelta, Utc};
+use diesel::prelude::*;
+use diesel_async::pooled_connection::bb8::Pool;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use log::{error, info, warn};
+use socketioxide::SocketIo;
+use std::time::Duration;
+use uuid::Uuid;
+
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Adds `amount` to the user's wallet as a new credit lot and records a credit
+/// transaction. Points without an `expires_at` never expire.
+/// Callers are expected to run this inside a database transaction.
+pub async fn credit_wallet(
+    conn: &mut AsyncPgConnection,
+    user_uuid: Uuid,
+    amount: i32,
+    description: &str,
+    expires_at: Option<NaiveDateTime>,
+) -> Result<Wallet, AppError> {
+    let wallet = lock_wallet(conn, user_uuid).await?;
+    let wallet = apply_change(conn, wallet, amount, TransactionType::Credit, description).await?;
+    open_lot(conn, wallet.id, amount, expires_at).await?;
+    Ok(wallet)
+}
+
+/// Removes `amount` from the user's wallet and records a debit transaction,
+/// rejecting the change if it would dip into points held for auction bids.
+/// Debits for an order remember the lots they drew from, see [`refund_order`].
+/// Callers are expected to run this inside a database transaction.
+pub async fn debit_wallet(
+    conn: &mut AsyncPgConnection,
+    user_uuid: Uuid,
+    amount: i32,
+    description: &str,
+    order_uuid: Option<Uuid>,
+) -> Result<Wallet, AppError> {
+    let wallet = lock_wallet(conn, user_uuid).await?;
+    if wallet.balance - wallet.held < amount {
+        return Err(insufficient_balance());
+    }
+    let wallet = apply_change(conn, wallet, -amount, TransactionType::Debit, description).await?;
+    let drawn = consume_lots(conn, wallet.id, amount).await?;
+
+    if let Some(order_uuid) = order_uuid {
+        let rows = drawn
+            .into_iter()
+            .map(|(lot_id, taken)| {
+                (
+                    private::order_credit_lots::order_uuid.eq(order_uuid),
+                    private::order_credit_lots::lot_id.eq(lot_id),
+                    private::order_credit_lots::amount.eq(taken),
+                )
+            })
+            .collect::<Vec<_>>();
+        diesel::insert_into(private::order_credit_lots::table)
+            .values(rows)
+            .execute(conn)
+            .await?;
+    }
+    Ok(wallet)
+}
+
+/// Returns an order's points to the lots it was paid from, keeping their original
+/// expiry, and records a credit transaction. Points that cannot be traced to a lot,
+/// such as those of orders placed before lots existed, come back as a lot that never expires.
+/// Callers are expected to run this inside a database transaction.
+pub async fn refund_order(
+    conn: &mut AsyncPgConnection,
+    user_uuid: Uuid,
+    order_uuid: Uuid,
+    amount: i32,
+    description: &str,
+) -> Result<Wallet, AppError> {
+    let wallet = lock_wallet(conn, user_uuid).await?;
+    let wallet = apply_change(conn, wallet, amount, TransactionType::Credit, description).await?;
+
+    let drawn = diesel::delete(
+        private::order_credit_lots::table
+            .filter(private::order_credit_lots::order_uuid.eq(order_uuid)),
+    )
+    .returning((
+        private::order_credit_lots::lot_id,
+        private::order_credit_lots::amount,
+    ))
+    .get_results::<(i64, i32)>(conn)
+    .await?;
+
+    let mut restored = 0;
+    for (lot_id, taken) in drawn {
+        diesel::update(private::credit_lots::table.find(lot_id))
+            .set(private::credit_lots::remaining.eq(private::credit_lots::remaining + taken))
+            .execute(conn)
+            .await?;
+        restored += taken;
+    }
+    if amount > restored {
+        open_lot(conn, wallet.id, amount - restored, None).await?;
+    }
+    Ok(wallet)
+}
+
+/// Reserves `amount` of the user's balance so it cannot be spent elsewhere.
+/// Held points are not a transaction, the balance itself is unchanged.
+/// Callers are expected to run this inside a database transaction.
+pub async fn hold_funds(
+    conn: &mut AsyncPgConnection,
+    user_uuid: Uuid,
+    amount: i32,
+) -> Result<Wallet, AppError> {
+    let wallet = lock_wallet(conn, user_uuid).await?;
+    if wallet.balance - wallet.held < amount {
+        return Err(insufficient_balance());
+    }
+    change_held(conn, wallet, amount).await
+}
+
+/// Returns points reserved by [`hold_funds`] to the spendable balance.
+/// Callers are expected to run this inside a database transaction.
+pub async fn release_funds(
+    conn: &mut AsyncPgConnection,
+    user_uuid: Uuid,
+    amount: i32,
+) -> Result<Wallet, AppError> {
+    let wallet = lock_wallet(conn, user_uuid).await?;
+    let amount = amount.min(wallet.held);
+    change_held(conn, wallet, -amount).await
+}
+
+async fn open_lot(
+    conn: &mut AsyncPgConnection,
+    wallet_id: i32,
+    amount: i32,
+    expires_at: Option<NaiveDateTime>,
+) -> Result<(), AppError> {
+    diesel::insert_into(private::credit_lots::table)
+        .values((
+            private::credit_lots::wallet_id.eq(wallet_id),
+            private::credit_lots::amount.eq(amount),
+            private::credit_lots::remaining.eq(amount),
+            private::credit_lots::expires_at.eq(expires_at),
+        ))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Takes `amount` out of the wallet's lots, oldest first, returning how much was
+/// taken from each lot.
+async fn consume_lots(
+    conn: &mut AsyncPgConnection,
+    wallet_id: i32,
+    amount: i32,
+) -> Result<Vec<(i64, i32)>, AppError> {
+    let lots = private::credit_lots::table
+        .filter(private::credit_lots::wallet_id.eq(wallet_id))
+        .filter(private::credit_lots::remaining.gt(0))
+        .select(CreditLot::as_select())
+        .order((
+            private::credit_lots::created_at.asc(),
+            private::credit_lots::id.asc(),
+        ))
+        .for_update()
+        .load::<CreditLot>(conn)
+        .await?;
+
+    let mut left = amount;
+    let mut drawn = vec![];
+    for lot in lots {
+        if left == 0 {
+            break;
+        }
+        let taken = left.min(lot.remaining);
+        diesel::update(private::credit_lots::table.find(lot.id))
+            .set(private::credit_lots::remaining.eq(lot.remaining - taken))
+            .execute(conn)
+            .await?;
+        drawn.push((lot.id, taken));
+        left -= taken;
+    }
+    if left > 0 {
+        warn!(
+            "Wallet {} lots were {} points short of its balance",
+            wallet_id, left
+        );
+    }
+    Ok(drawn)
+}
+
+/// Runs the point expiry every night at midnight. Runs until the process exits.
+pub async fn run_expiry_job(pool: Pool<AsyncPgConnection>, io: SocketIo) {
+    let now = Utc::now().naive_utc();
+    let midnight = (now.date() + TimeDelta::days(1)).and_time(NaiveTime::MIN);
+    let until_midnight = (midnight - now).to_std().unwrap_or_default();
+    let mut interval = tokio::time::interval_at(
+        tokio::time::Instant::now() + until_midnight,
+        EXPIRY_INTERVAL,
+    );
+    loop {
+        interval.tick().await;
+        if let Err(e) = expire_lapsed_lots(&pool, &io).await {
+            error!("Point expiry failed: {:?}", e);
+        }
+    }
+}
+
+async fn expire_lapsed_lots(pool: &Pool<AsyncPgConnection>, io: &SocketIo) -> Result<(), AppError> {
+    let mut con = pool.get().await?;
+    let now = Utc::now().naive_utc();
+    let wallet_ids = private::credit_lots::table
+        .filter(private::credit_lots::remaining.gt(0))
+        .filter(private::credit_lots::expires_at.le(now))
+        .select(private::credit_lots::wallet_id)
+        .distinct()
+        .load::<i32>(&mut con)
+        .await?;
+
+    // Each wallet expires on its own, so one failure does not hold up the rest
+    for wallet_id in wallet_ids {
+        let expired = con
+            .transaction(|conn| {
+                async move { expire_wallet(conn, wallet_id, now).await }.scope_boxed()
+            })
+            .await;
+        match expired {
+            Ok(Some(wallet)) => emit_balance_changed(io, wallet.user_uuid, wallet.balance),
+            Ok(None) => {}
+            Err(e) => error!("Unable to expire points for wallet {}: {:?}", wallet_id, e),
+        }
+    }
+    Ok(())
+}
+
+/// Removes the remaining points of lots that lapsed by `now` and records them as
+/// one expiry transaction. Points held for auction bids are left until released.
+async fn expire_wallet(
+    conn: &mut AsyncPgConnection,
+    wallet_id: i32,
+    now: NaiveDateTime,
+) -> Result<Option<Wallet>, AppError> {
+    let wallet = private::wallets::table
+        .find(wallet_id)
+        .select(Wallet::as_select())
+        .for_update()
+        .first::<Wallet>(conn)
+        .await?;
+    let lots = private::credit_lots::table
+        .filter(private::credit_lots::wallet_id.eq(wallet_id))
+        .filter(private::credit_lots::remaining.gt(0))
+        .filter(private::credit_lots::expires_at.le(now))
+        .select(CreditLot::as_select())
+        .order(private::credit_lots::expires_at.asc())
+        .for_update()
+        .load::<CreditLot>(conn)
+        .await?;
+
+    let mut spendable = wallet.balance - wallet.held;
+    let mut expired = 0;
+    for lot in lots {
+        let taken = spendable.min(lot.remaining);
+        if taken <= 0 {
+            break;
+        }
+        diesel::update(private::credit_lots::table.find(lot.id))
+            .set(private::credit_lots::remaining.eq(lot.remaining - taken))
+            .execute(conn)
+            .await?;
+        spendable -= taken;
+        expired += taken;
+    }
+    if expired == 0 {
+        return Ok(None);
+    }
+
+    info!("Expired {} points from wallet {}", expired, wallet_id);
+    let wallet = apply_change(
+        conn,
+        wallet,
+        -expired,
+        TransactionType::Expiry,
+        "Points expired",
+    )
+    .await?;
+    Ok(Some(wallet))
+}
+
+fn insufficient_balance() -> AppError {
+    let errors = vec!["Insufficient balance".to_string()];
+    AppError::bad_request::<ClientErrorMessages>(DataValidationError { errors }.into())
+}
+
+async fn lock_wallet(conn: &mut AsyncPgConnection, user_uuid: Uuid) -> Result<Wallet, AppError> {
+    private::wallets::table
+        .filter(private::wallets::user_uuid.eq(user_uuid))
+        .select(Wallet::as_select())
+        .for_update()
+        .first::<Wallet>(conn)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)
+}
+
+async fn change_held(
+    conn: &mut AsyncPgConnection,
+    wallet: Wallet,
+    delta: i32,
+) -> Result<Wallet, AppError> {
+    let wallet = diesel::update(private::wallets::table.find(wallet.id))
+        .set((
+            private::wallets::held.eq(private::wallets::held + delta),
+            private::wallets::updated_at.eq(diesel::dsl::now),
+        ))
+        .returning(Wallet::as_returning())
+        .get_result::<Wallet>(conn)
+        .await?;
+    Ok(wallet)
+}
+
+async fn apply_change(
+    conn: &mut AsyncPgConnection,
+    wallet: Wallet,
+    delta: i32,
+    transaction_type: TransactionType,
+    description: &str,
+) -> Result<Wallet, AppError> {
+    let wallet = diesel::update(private::wallets::table.find(wallet.id))
+        .set((
+            private::wallets::balance.eq(private::wallets::balance + delta),
+            private::wallets::updated_at.eq(diesel::dsl::now),
+        ))
+        .returning(Wallet::as_returning())
+        .get_result::<Wallet>(conn)
+        .await?;
+
+    diesel::insert_into(private::transactions::table)
+        .values((
+            private::transactions::wallet_id.eq(wallet.id),
+            private::transactions::amount.eq(delta.abs()),
+            private::transactions::transaction_type.eq(transaction_type),
+            private::transactions::description.eq(description),
+        ))
+        .execute(conn)
+        .await?;
+
+    Ok(wallet)
+}
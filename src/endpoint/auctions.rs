@@ -0,0 +1,327 @@
+use crate::backend::auction::{lock_auction, release_high_bid};
+use crate::backend::audit::{record_audit, snapshot};
+use crate::backend::wallet::hold_funds;
+use crate::models::auctions::{Auction, AuctionBid, AuctionStatus};
+use crate::models::audit::AuditAction;
+use crate::paseto::AuthTokenClaims;
+use crate::req_res::auctions::{
+    AuctionQueryParams, AuctionRes, BidRes, NewAuction, NewAuctionReq, PlaceBid, PlaceBidReq,
+};
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use crate::schema::private;
+use crate::websocket::{emit_auction_closed, emit_bid_placed};
+use crate::AppState;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use log::error;
+use pasetors::claims::Claims;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub fn get_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .nest(
+            "/auctions/",
+            Router::new()
+                .route("/", get(get_open_auctions))
+                .route("/{uid}", get(get_auction))
+                .route("/{uid}/bids", get(get_bids).post(place_bid)),
+        )
+        .nest(
+            "/inventory/auctions/",
+            Router::new()
+                .route("/", get(get_all_auctions).post(create_auction))
+                .route("/{uid}/bids", get(get_all_bids))
+                .route("/{uid}/cancel", post(cancel_auction)),
+        )
+}
+
+/// Auctions that have not closed yet, including ones that have not started, ending soonest first.
+async fn get_open_auctions(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let res = private::auctions::table
+        .filter(private::auctions::status.eq(AuctionStatus::Open))
+        .select(Auction::as_select())
+        .order(private::auctions::ends_at.asc())
+        .load::<Auction>(&mut con)
+        .await?
+        .into_iter()
+        .map(|auction| AuctionRes::new(auction, claims.user_uid))
+        .collect::<Vec<AuctionRes>>();
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+async fn get_auction(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let auction = private::auctions::table
+        .find(uid)
+        .select(Auction::as_select())
+        .first::<Auction>(&mut con)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(AuctionRes::new(auction, claims.user_uid)),
+    ))
+}
+
+/// Bid history without the other bidders, highest first.
+async fn get_bids(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let res = private::auction_bids::table
+        .filter(private::auction_bids::auction_uuid.eq(uid))
+        .select(AuctionBid::as_select())
+        .order(private::auction_bids::amount.desc())
+        .load::<AuctionBid>(&mut con)
+        .await?
+        .into_iter()
+        .map(|bid| BidRes::new(bid, claims.user_uid))
+        .collect::<Vec<BidRes>>();
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+/// Places a bid above the current high bid. The amount is held in the bidder's
+/// wallet until they are outbid or the auction settles.
+async fn place_bid(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+    Json(payload): Json<PlaceBidReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+    let req: PlaceBid = payload.try_into()?;
+
+    let (auction, outbid) = con
+        .transaction(|conn| {
+            async move {
+                // Locking the auction serialises bids and keeps the settler out
+                let before = lock_auction(conn, uid).await?;
+                let now = Utc::now().naive_utc();
+                if before.status != AuctionStatus::Open || before.ends_at <= now {
+                    return Err(auction_error("Auction has closed"));
+                }
+                if before.starts_at > now {
+                    return Err(auction_error("Auction has not started"));
+                }
+                if before.high_bid.is_some_and(|bid| req.amount <= bid) {
+                    return Err(auction_error("Bid must be higher than the current bid"));
+                }
+
+                // Raising your own bid releases the old hold before the new one is placed
+                release_high_bid(conn, &before).await?;
+                hold_funds(conn, claims.user_uid, req.amount).await?;
+
+                diesel::insert_into(private::auction_bids::table)
+                    .values((
+                        private::auction_bids::auction_uuid.eq(uid),
+                        private::auction_bids::user_uuid.eq(claims.user_uid),
+                        private::auction_bids::amount.eq(req.amount),
+                    ))
+                    .execute(conn)
+                    .await?;
+
+                let after = diesel::update(private::auctions::table.find(uid))
+                    .set((
+                        private::auctions::high_bid.eq(req.amount),
+                        private::auctions::high_bidder.eq(claims.user_uid),
+                    ))
+                    .returning(Auction::as_returning())
+                    .get_result::<Auction>(conn)
+                    .await?;
+
+                let outbid = before
+                    .high_bidder
+                    .filter(|bidder| *bidder != claims.user_uid);
+                Ok::<(Auction, Option<Uuid>), AppError>((after, outbid))
+            }
+            .scope_boxed()
+        })
+        .await?;
+    emit_bid_placed(&state.io, auction.uuid, req.amount, outbid);
+
+    Ok((
+        StatusCode::CREATED,
+        Json(AuctionRes::new(auction, claims.user_uid)),
+    ))
+}
+
+async fn get_all_auctions(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AuctionQueryParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+
+    let mut query = private::auctions::table
+        .select(Auction::as_select())
+        .order(private::auctions::ends_at.desc())
+        .into_boxed();
+
+    if let Some(status) = params.status {
+        query = query.filter(private::auctions::status.eq(status));
+    }
+
+    let auctions = query.load::<Auction>(&mut con).await?;
+
+    Ok((StatusCode::OK, Json(auctions)))
+}
+
+async fn create_auction(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+    Json(payload): Json<NewAuctionReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+    let req: NewAuction = payload.try_into()?;
+
+    let auction = con
+        .transaction(|conn| {
+            async move {
+                let auction = diesel::insert_into(private::auctions::table)
+                    .values((&req, private::auctions::created_by.eq(claims.user_uid)))
+                    .returning(Auction::as_returning())
+                    .get_result::<Auction>(conn)
+                    .await?;
+
+                record_audit(
+                    conn,
+                    claims.user_uid,
+                    AuditAction::AuctionCreated,
+                    &auction.uuid.to_string(),
+                    None,
+                    snapshot(&auction),
+                )
+                .await?;
+                Ok::<Auction, AppError>(auction)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(auction)))
+}
+
+async fn get_all_bids(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+
+    let bids = private::auction_bids::table
+        .filter(private::auction_bids::auction_uuid.eq(uid))
+        .select(AuctionBid::as_select())
+        .order(private::auction_bids::amount.desc())
+        .load::<AuctionBid>(&mut con)
+        .await?;
+
+    Ok((StatusCode::OK, Json(bids)))
+}
+
+/// Withdraws an open auction and returns the held high bid to the bidder.
+async fn cancel_auction(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let auction = con
+        .transaction(|conn| {
+            async move {
+                let before = lock_auction(conn, uid).await?;
+                if before.status != AuctionStatus::Open {
+                    return Err(auction_error("Auction has already closed"));
+                }
+                release_high_bid(conn, &before).await?;
+
+                let after = diesel::update(private::auctions::table.find(uid))
+                    .set(private::auctions::status.eq(AuctionStatus::Cancelled))
+                    .returning(Auction::as_returning())
+                    .get_result::<Auction>(conn)
+                    .await?;
+
+                record_audit(
+                    conn,
+                    claims.user_uid,
+                    AuditAction::AuctionCancelled,
+                    &uid.to_string(),
+                    snapshot(&before),
+                    snapshot(&after),
+                )
+                .await?;
+                Ok::<Auction, AppError>(after)
+            }
+            .scope_boxed()
+        })
+        .await?;
+    emit_auction_closed(&state.io, auction.uuid, auction.status, auction.high_bid);
+
+    Ok((StatusCode::OK, Json(auction)))
+}
+
+fn auction_error(message: &str) -> AppError {
+    let errors = vec![message.to_string()];
+    AppError::bad_request::<ClientErrorMessages>(DataValidationError { errors }.into())
+}
@@ -0,0 +1,70 @@
+use crate::models::audit::AuditEntry;
+use crate::req_res::audit::{AuditQuery, AuditQueryParams};
+use crate::req_res::{AppError, Paginated};
+use crate::schema::private;
+use crate::AppState;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Json, Router};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use std::sync::Arc;
+
+pub fn get_routes() -> Router<Arc<AppState>> {
+    Router::new().nest("/audit/", Router::new().route("/", get(get_audit_log)))
+}
+
+async fn get_audit_log(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AuditQueryParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let req: AuditQuery = params.try_into()?;
+
+    let filtered = || {
+        let mut query = private::audit_log::table.into_boxed();
+        if let Some(actor) = req.actor {
+            query = query.filter(private::audit_log::actor_uuid.eq(actor));
+        }
+        if let Some(action) = &req.action {
+            query = query.filter(private::audit_log::action.eq(action));
+        }
+        if let Some(target_type) = &req.target_type {
+            query = query.filter(private::audit_log::target_type.eq(target_type));
+        }
+        if let Some(target_id) = &req.target_id {
+            query = query.filter(private::audit_log::target_id.eq(target_id));
+        }
+        if let Some(from) = req.from {
+            query = query.filter(private::audit_log::created_at.ge(from));
+        }
+        if let Some(to) = req.to {
+            query = query.filter(private::audit_log::created_at.lt(to));
+        }
+        query
+    };
+
+    let total: i64 = filtered().count().get_result(&mut con).await?;
+    let items = filtered()
+        .select(AuditEntry::as_select())
+        .order((
+            private::audit_log::created_at.desc(),
+            private::audit_log::id.desc(),
+        ))
+        .limit(req.per_page)
+        .offset((req.page - 1) * req.per_page)
+        .load::<AuditEntry>(&mut con)
+        .await?;
+
+    let res = Paginated {
+        items,
+        page: req.page,
+        per_page: req.per_page,
+        total,
+    };
+
+    Ok((StatusCode::OK, Json(res)))
+}
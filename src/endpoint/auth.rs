@@ -1,10 +1,19 @@
 use crate::backend::pw_reset::{
     new_password_reset_req, verify_password_reset_otp, verify_reset_token, PasswordResetReq,
 };
-use crate::helper::{hash_password, is_bad_mail, validate_token, verify_password};
+use crate::backend::rate_limit::{
+    check_login_lockout, check_rate_limit, clear_login_failures, record_login_failure,
+    LOGIN_PER_ACCOUNT, LOGIN_PER_IP, OTP_PER_IP, OTP_PER_SESSION, PW_RESET_PER_IP,
+    PW_RESET_PER_PHONE,
+};
+use crate::backend::sessions::{revoke_user_sessions, rotate_session, start_session};
+use crate::helper::{client_ip, hash_password, is_bad_mail, validate_token, verify_password};
 use crate::models::user::User;
 use crate::models::wallet::Wallet;
-use crate::paseto::AuthTokenClaims;
+use crate::notifications::queue::enqueue;
+use crate::notifications::templates::Template;
+use crate::notifications::Recipient;
+use crate::paseto::{AuthTokenClaims, TokenPurpose};
 use crate::req_res::auth::{
     AppInitRequest, NewTokens, NewUser, PasswordResetOtpReq, PasswordResetRequest,
     PasswordResetRes, PwResetOtpValidated, ResetParams, UserAuthRequest,
@@ -18,8 +27,8 @@ use crate::schema::private::users::resident_id;
 use crate::schema::private::users::uuid as SqlUuid;
 use crate::utils::generate_otp;
 use crate::AppState;
-use axum::extract::{Path, Query, State};
-use axum::http::StatusCode;
+use axum::extract::{ConnectInfo, Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::IntoResponse;
 use axum::routing::{get, post};
 use axum::{Json, Router};
@@ -32,7 +41,8 @@ use diesel::prelude::*;
 use diesel::result::Error;
 use diesel_async::scoped_futures::ScopedFutureExt;
 use diesel_async::{AsyncConnection, RunQueryDsl};
-use log::{error, warn};
+use log::{debug, error, info, warn};
+use std::net::SocketAddr;
 use std::str::FromStr;
 use std::sync::Arc;
 use uuid::Uuid;
@@ -50,9 +60,17 @@ pub fn get_scope() -> Router<Arc<AppState>> {
 
 async fn login(
     State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(payload): Json<UserAuthRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     let pool = &state.postgres_pool;
+    let redis = &state.redis_client;
+    let ip = client_ip(&headers, addr, state.config.trust_proxy);
+    check_rate_limit(redis, &LOGIN_PER_IP, &ip.to_string()).await?;
+    check_rate_limit(redis, &LOGIN_PER_ACCOUNT, &payload.resident_id).await?;
+    check_login_lockout(redis, &payload.resident_id).await?;
+
     let mut con = pool.get().await?;
     let user_result = users
         .filter(resident_id.eq(&payload.resident_id))
@@ -61,12 +79,24 @@ async fn login(
 
     match user_result {
         Ok(user) => {
-            verify_password(&user.password, &payload.password)?;
-            let res: UserAuthenticationResponse = user.into();
+            if let Err(e) = verify_password(&user.password, &payload.password) {
+                record_login_failure(redis, &payload.resident_id).await?;
+                return Err(e);
+            }
+            clear_login_failures(redis, &payload.resident_id).await?;
+            if !user.active {
+                return Err(AppError::account_suspended());
+            }
+            let refresh_jti = start_session(redis, user.uuid).await?;
+            let res: UserAuthenticationResponse = (user, refresh_jti).into();
             Ok((StatusCode::OK, Json(res)))
         }
         Err(e) => match e {
-            Error::NotFound => Err(AppError::unauthorized()),
+            Error::NotFound => {
+                // Count unknown IDs too so probing for valid accounts is throttled the same way
+                record_login_failure(redis, &payload.resident_id).await?;
+                Err(AppError::unauthorized())
+            }
             _ => Err(AppError::from(e)),
         },
     }
@@ -128,7 +158,8 @@ async fn init_app(
                 })
                 .await
                 .map_err(AppError::from)?;
-            let res: UserAuthenticationResponse = created_user.into();
+            let refresh_jti = start_session(&state.redis_client, created_user.uuid).await?;
+            let res: UserAuthenticationResponse = (created_user, refresh_jti).into();
             Ok((StatusCode::OK, Json(res)))
         }
     } else {
@@ -137,20 +168,65 @@ async fn init_app(
 }
 
 async fn refresh_token(
+    State(state): State<Arc<AppState>>,
     TypedHeader(Authorization(bearer)): TypedHeader<Authorization<Bearer>>,
 ) -> Result<impl IntoResponse, AppError> {
-    let (_, claims) = validate_token(bearer.token()).ok_or(AppError::unauthorized())?;
+    let (_, claims) =
+        validate_token(bearer.token(), TokenPurpose::Refresh).ok_or(AppError::unauthorized())?;
     let claims = AuthTokenClaims::try_from(&claims).map_err(|_| AppError::unauthorized())?;
-    let res = NewTokens::new(claims.user_uid, claims.role);
+    if claims.purpose != TokenPurpose::Refresh {
+        return Err(AppError::unauthorized());
+    }
+
+    let pool = &state.postgres_pool;
+    let redis = &state.redis_client;
+    let mut con = pool.get().await?;
+    let user = users
+        .filter(SqlUuid.eq(claims.user_uid))
+        .first::<User>(&mut con)
+        .await
+        .optional()?;
+    let user = match user {
+        Some(user) if user.active => user,
+        Some(_) => {
+            revoke_user_sessions(redis, claims.user_uid).await?;
+            return Err(AppError::account_suspended());
+        }
+        None => {
+            revoke_user_sessions(redis, claims.user_uid).await?;
+            return Err(AppError::unauthorized());
+        }
+    };
+
+    let (session, refresh_jti) = rotate_session(redis, claims.jti).await?;
+    if session.user_uuid != user.uuid {
+        return Err(AppError::unauthorized());
+    }
+    debug!(
+        "Rotated refresh token {} in family {} for {}",
+        claims.jti, session.family_id, user.uuid
+    );
+    if user.role != claims.role {
+        info!(
+            "Role for {} changed from {:?} to {:?}, issuing updated tokens",
+            user.uuid, claims.role, user.role
+        );
+    }
+    let res = NewTokens::new(user.uuid, user.role, refresh_jti);
     Ok((StatusCode::OK, Json(res)))
 }
 
 async fn initiate_password_reset(
     State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(payload): Json<PasswordResetRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     let pool = &state.postgres_pool;
     let redis = &state.redis_client;
+    let ip = client_ip(&headers, addr, state.config.trust_proxy);
+    check_rate_limit(redis, &PW_RESET_PER_IP, &ip.to_string()).await?;
+    check_rate_limit(redis, &PW_RESET_PER_PHONE, &payload.phone).await?;
     let mut conn = pool.get().await?;
     let matched_user: Option<User> = private::users::table
         .filter(private::users::phone.eq(&payload.phone))
@@ -170,8 +246,13 @@ async fn initiate_password_reset(
 
     if let Some(matched_user) = matched_user {
         let otp = generate_otp();
-        println!("pw reset otp: {}", &otp);
         new_password_reset_req(redis, session_uid, &otp, expire.clone(), matched_user.uuid).await?;
+        let message = Template::PasswordResetOtp {
+            otp: &otp,
+            expires_in_minutes: 10,
+        }
+        .render(Recipient::Sms(matched_user.phone));
+        enqueue(redis, message).await?;
         Ok((StatusCode::OK, Json(res)))
     } else {
         warn!("Invalid user");
@@ -181,10 +262,15 @@ async fn initiate_password_reset(
 
 async fn verify_pw_otp(
     State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(payload): Json<PasswordResetOtpReq>,
 ) -> Result<impl IntoResponse, AppError> {
     let redis = &state.redis_client;
+    let ip = client_ip(&headers, addr, state.config.trust_proxy);
+    check_rate_limit(redis, &OTP_PER_IP, &ip.to_string()).await?;
     let req: PwResetOtpValidated = payload.try_into()?;
+    check_rate_limit(redis, &OTP_PER_SESSION, &req.session_uid.to_string()).await?;
     let result = verify_password_reset_otp(redis, req.session_uid, &req.otp).await?;
     Ok((StatusCode::OK, Json(result)))
 }
@@ -212,6 +298,7 @@ async fn complete_pw_reset(
                 ))
                 .execute(&mut conn)
                 .await?;
+            revoke_user_sessions(redis, uuid).await?;
 
             Ok((StatusCode::OK, ()))
         }
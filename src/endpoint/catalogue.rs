@@ -0,0 +1,352 @@
+use crate::backend::audit::{record_audit, snapshot};
+use crate::backend::catalogue::{category_subtree, ensure_category};
+use crate::models::audit::AuditAction;
+use crate::models::products::{Category, Tag};
+use crate::paseto::AuthTokenClaims;
+use crate::req_res::inventory::{
+    NewCategory, NewCategoryReq, NewTag, TagReq, UpdateCategory, UpdateCategoryReq,
+};
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use crate::schema::private;
+use crate::AppState;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{patch, post};
+use axum::{Extension, Json, Router};
+use diesel::result::{DatabaseErrorKind, Error};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use log::error;
+use pasetors::claims::Claims;
+use std::sync::Arc;
+
+pub fn get_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .nest(
+            "/inventory/categories/",
+            Router::new()
+                .route("/", post(create_category))
+                .route("/{id}", patch(update_category).delete(delete_category)),
+        )
+        .nest(
+            "/inventory/tags/",
+            Router::new()
+                .route("/", post(create_tag))
+                .route("/{id}", patch(rename_tag).delete(delete_tag)),
+        )
+}
+
+async fn create_category(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+    Json(payload): Json<NewCategoryReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+    let req: NewCategory = payload.try_into()?;
+
+    let category = con
+        .transaction(|conn| {
+            async move {
+                if let Some(parent_id) = req.parent_id {
+                    ensure_category(conn, parent_id).await?;
+                }
+                let category = diesel::insert_into(private::categories::table)
+                    .values(&req)
+                    .returning(Category::as_returning())
+                    .get_result::<Category>(conn)
+                    .await
+                    .map_err(name_taken)?;
+
+                record_audit(
+                    conn,
+                    claims.user_uid,
+                    AuditAction::CategoryCreated,
+                    &category.id.to_string(),
+                    None,
+                    snapshot(&category),
+                )
+                .await?;
+                Ok::<Category, AppError>(category)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(category)))
+}
+
+async fn update_category(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Extension(c): Extension<Option<Claims>>,
+    Json(payload): Json<UpdateCategoryReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+    let req: UpdateCategory = payload.try_into()?;
+
+    let category = con
+        .transaction(|conn| {
+            async move {
+                let before = lock_category(conn, id).await?;
+
+                if let Some(Some(parent_id)) = req.parent_id {
+                    ensure_category(conn, parent_id).await?;
+                    // A category cannot be moved underneath itself
+                    if category_subtree(conn, id).await?.contains(&parent_id) {
+                        let errors =
+                            vec!["Category cannot be its own parent or ancestor".to_string()];
+                        return Err(AppError::bad_request::<ClientErrorMessages>(
+                            DataValidationError { errors }.into(),
+                        ));
+                    }
+                }
+
+                let category = diesel::update(private::categories::table)
+                    .filter(private::categories::id.eq(id))
+                    .set(&req)
+                    .returning(Category::as_returning())
+                    .get_result::<Category>(conn)
+                    .await
+                    .map_err(name_taken)?;
+
+                record_audit(
+                    conn,
+                    claims.user_uid,
+                    AuditAction::CategoryUpdated,
+                    &id.to_string(),
+                    snapshot(&before),
+                    snapshot(&category),
+                )
+                .await?;
+                Ok::<Category, AppError>(category)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok((StatusCode::OK, Json(category)))
+}
+
+/// Products in the category are left uncategorised, subcategories have to be moved first.
+async fn delete_category(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    con.transaction(|conn| {
+        async move {
+            let before = lock_category(conn, id).await?;
+
+            let children: i64 = private::categories::table
+                .filter(private::categories::parent_id.eq(id))
+                .count()
+                .get_result(conn)
+                .await?;
+            if children > 0 {
+                let errors = vec!["Category still has subcategories".to_string()];
+                return Err(AppError::bad_request::<ClientErrorMessages>(
+                    DataValidationError { errors }.into(),
+                ));
+            }
+
+            diesel::delete(private::categories::table)
+                .filter(private::categories::id.eq(id))
+                .execute(conn)
+                .await?;
+
+            record_audit(
+                conn,
+                claims.user_uid,
+                AuditAction::CategoryDeleted,
+                &id.to_string(),
+                snapshot(&before),
+                None,
+            )
+            .await
+        }
+        .scope_boxed()
+    })
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn create_tag(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+    Json(payload): Json<TagReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+    let req: NewTag = payload.try_into()?;
+
+    let tag = con
+        .transaction(|conn| {
+            async move {
+                let tag = diesel::insert_into(private::tags::table)
+                    .values(&req)
+                    .returning(Tag::as_returning())
+                    .get_result::<Tag>(conn)
+                    .await
+                    .map_err(name_taken)?;
+
+                record_audit(
+                    conn,
+                    claims.user_uid,
+                    AuditAction::TagCreated,
+                    &tag.id.to_string(),
+                    None,
+                    snapshot(&tag),
+                )
+                .await?;
+                Ok::<Tag, AppError>(tag)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(tag)))
+}
+
+async fn rename_tag(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Extension(c): Extension<Option<Claims>>,
+    Json(payload): Json<TagReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+    let req: NewTag = payload.try_into()?;
+
+    let tag = con
+        .transaction(|conn| {
+            async move {
+                let before = lock_tag(conn, id).await?;
+                let tag = diesel::update(private::tags::table)
+                    .filter(private::tags::id.eq(id))
+                    .set(&req)
+                    .returning(Tag::as_returning())
+                    .get_result::<Tag>(conn)
+                    .await
+                    .map_err(name_taken)?;
+
+                record_audit(
+                    conn,
+                    claims.user_uid,
+                    AuditAction::TagUpdated,
+                    &id.to_string(),
+                    snapshot(&before),
+                    snapshot(&tag),
+                )
+                .await?;
+                Ok::<Tag, AppError>(tag)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok((StatusCode::OK, Json(tag)))
+}
+
+async fn delete_tag(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    con.transaction(|conn| {
+        async move {
+            let before = lock_tag(conn, id).await?;
+            diesel::delete(private::tags::table)
+                .filter(private::tags::id.eq(id))
+                .execute(conn)
+                .await?;
+
+            record_audit(
+                conn,
+                claims.user_uid,
+                AuditAction::TagDeleted,
+                &id.to_string(),
+                snapshot(&before),
+                None,
+            )
+            .await
+        }
+        .scope_boxed()
+    })
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn lock_category(conn: &mut AsyncPgConnection, id: i32) -> Result<Category, AppError> {
+    private::categories::table
+        .filter(private::categories::id.eq(id))
+        .select(Category::as_select())
+        .for_update()
+        .first::<Category>(conn)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)
+}
+
+async fn lock_tag(conn: &mut AsyncPgConnection, id: i32) -> Result<Tag, AppError> {
+    private::tags::table
+        .filter(private::tags::id.eq(id))
+        .select(Tag::as_select())
+        .for_update()
+        .first::<Tag>(conn)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)
+}
+
+/// Category and tag names are unique, report clashes as a validation error.
+fn name_taken(err: Error) -> AppError {
+    match err {
+        Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            let errors = vec!["Name is already in use".to_string()];
+            AppError::bad_request::<ClientErrorMessages>(DataValidationError { errors }.into())
+        }
+        err => err.into(),
+    }
+}
@@ -0,0 +1,235 @@
+use crate::backend::audit::{record_audit, snapshot};
+use crate::backend::credit_rules::pending_credits;
+use crate::models::audit::AuditAction;
+use crate::models::credit_rules::CreditRule;
+use crate::paseto::AuthTokenClaims;
+use crate::req_res::credit_rules::{
+    CreditPeriodPreview, CreditRecipient, CreditRulePreview, NewCreditRule, NewCreditRuleReq,
+};
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use crate::schema::private;
+use crate::AppState;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use log::error;
+use pasetors::claims::Claims;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub fn get_routes() -> Router<Arc<AppState>> {
+    Router::new().nest(
+        "/users/credit-rules/",
+        Router::new()
+            .route("/", get(get_credit_rules).post(create_credit_rule))
+            .route("/{uid}/preview", get(preview_credit_rule))
+            .route("/{uid}/pause", post(pause_credit_rule))
+            .route("/{uid}/resume", post(resume_credit_rule)),
+    )
+}
+
+async fn get_credit_rules(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+
+    let rules = private::credit_rules::table
+        .select(CreditRule::as_select())
+        .order(private::credit_rules::created_at.desc())
+        .load::<CreditRule>(&mut con)
+        .await?;
+
+    Ok((StatusCode::OK, Json(rules)))
+}
+
+async fn create_credit_rule(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+    Json(payload): Json<NewCreditRuleReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+    let paused = payload.paused;
+    let mut req: NewCreditRule = payload.try_into()?;
+    if paused {
+        req.paused_at = Some(Utc::now().naive_utc());
+    }
+
+    let rule = con
+        .transaction(|conn| {
+            async move {
+                let rule = diesel::insert_into(private::credit_rules::table)
+                    .values((&req, private::credit_rules::created_by.eq(claims.user_uid)))
+                    .returning(CreditRule::as_returning())
+                    .get_result::<CreditRule>(conn)
+                    .await?;
+
+                record_audit(
+                    conn,
+                    claims.user_uid,
+                    AuditAction::CreditRuleCreated,
+                    &rule.uuid.to_string(),
+                    None,
+                    snapshot(&rule),
+                )
+                .await?;
+                Ok::<CreditRule, AppError>(rule)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(rule)))
+}
+
+/// Dry run of the rule: who the scheduler would credit right now. Works on
+/// paused rules too, so a new rule can be checked before it is resumed.
+async fn preview_credit_rule(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+
+    let rule = private::credit_rules::table
+        .find(uid)
+        .select(CreditRule::as_select())
+        .first::<CreditRule>(&mut con)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)?;
+
+    let periods = pending_credits(&mut con, &rule, Utc::now().naive_utc())
+        .await?
+        .into_iter()
+        .map(|(period, users)| CreditPeriodPreview {
+            period,
+            recipients: users.into_iter().map(CreditRecipient::from).collect(),
+        })
+        .collect::<Vec<CreditPeriodPreview>>();
+    let recipients = periods
+        .iter()
+        .map(|period| period.recipients.len() as i64)
+        .sum::<i64>();
+    let preview = CreditRulePreview {
+        periods,
+        amount: rule.amount,
+        total: i64::from(rule.amount) * recipients,
+    };
+
+    Ok((StatusCode::OK, Json(preview)))
+}
+
+async fn pause_credit_rule(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let rule = con
+        .transaction(|conn| {
+            async move {
+                let paused_at = Some(Utc::now().naive_utc());
+                set_paused(conn, claims.user_uid, uid, paused_at).await
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok((StatusCode::OK, Json(rule)))
+}
+
+/// Resumes a paused rule. Months that came due while a monthly rule was paused are
+/// still paid, while birthdays and one-off runs only within their grace period.
+/// Preview the rule first to see what that will be.
+async fn resume_credit_rule(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let rule = con
+        .transaction(|conn| {
+            async move { set_paused(conn, claims.user_uid, uid, None).await }.scope_boxed()
+        })
+        .await?;
+
+    Ok((StatusCode::OK, Json(rule)))
+}
+
+/// Pauses the rule when `paused_at` is set and resumes it otherwise, rejecting
+/// changes that would leave it as it was.
+async fn set_paused(
+    conn: &mut AsyncPgConnection,
+    actor: Uuid,
+    uid: Uuid,
+    paused_at: Option<NaiveDateTime>,
+) -> Result<CreditRule, AppError> {
+    let before = private::credit_rules::table
+        .find(uid)
+        .select(CreditRule::as_select())
+        .for_update()
+        .first::<CreditRule>(conn)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)?;
+
+    if before.paused_at.is_some() == paused_at.is_some() {
+        let state = if paused_at.is_some() {
+            "paused"
+        } else {
+            "active"
+        };
+        let errors = vec![format!("Credit rule is already {}", state)];
+        return Err(AppError::bad_request::<ClientErrorMessages>(
+            DataValidationError { errors }.into(),
+        ));
+    }
+
+    let after = diesel::update(private::credit_rules::table.find(uid))
+        .set(private::credit_rules::paused_at.eq(paused_at))
+        .returning(CreditRule::as_returning())
+        .get_result::<CreditRule>(conn)
+        .await?;
+
+    let action = match paused_at {
+        Some(_) => AuditAction::CreditRulePaused,
+        None => AuditAction::CreditRuleResumed,
+    };
+    record_audit(
+        conn,
+        actor,
+        action,
+        &uid.to_string(),
+        snapshot(&before),
+        snapshot(&after),
+    )
+    .await?;
+    Ok(after)
+}
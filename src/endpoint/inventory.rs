@@ -1,39 +1,96 @@
-use crate::helper::save_product_image;
-use crate::models::products::Product;
-use crate::req_res::inventory::{NewProduct, NewProductReq, UpdateProduct, UpdateProductReq};
-use crate::req_res::AppError;
+use crate::backend::audit::{record_audit, snapshot};
+use crate::backend::catalogue::{ensure_category, load_tags, set_product_tags};
+use crate::backend::csv_import::read_rows;
+use crate::backend::product_csv::{read_images, split_tags, write_catalogue};
+use crate::backend::stock::{find_low_stock, record_movement};
+use crate::helper::{remove_orphaned_product_images, remove_product_images, save_product_image};
+use crate::models::audit::AuditAction;
+use crate::models::products::{Product, ProductRecord};
+use crate::models::stock::{NewStockMovement, StockMovement, StockMovementKind};
+use crate::paseto::AuthTokenClaims;
+use crate::req_res::inventory::{
+    ImportQuery, ImportReport, MovementQuery, MovementQueryParams, NewProduct, NewProductReq,
+    ProductChanges, ProductCsvRow, StockAdjustment, StockAdjustmentReq, UpdateProduct,
+    UpdateProductReq,
+};
+use crate::req_res::{
+    AppError, ClientErrorMessages, DataValidationError, ImportRowError, Paginated,
+};
 use crate::schema::private;
+use crate::websocket::emit_stock_changed;
 use crate::AppState;
-use axum::extract::{Multipart, Path, State};
-use axum::http::StatusCode;
+use axum::body::Body;
+use axum::extract::{DefaultBodyLimit, Multipart, Path, Query, State};
+use axum::http::{header, StatusCode};
 use axum::response::IntoResponse;
-use axum::routing::{patch, post};
-use axum::{Json, Router};
+use axum::routing::{delete, get, patch, post};
+use axum::{Extension, Json, Router};
 use bytes::Bytes;
+use chrono::NaiveDateTime;
 use diesel::ExpressionMethods;
 use diesel::OptionalExtension;
 use diesel::QueryDsl;
-use diesel_async::RunQueryDsl;
+use diesel::SelectableHelper;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use log::{error, info};
+use pasetors::claims::Claims;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use tokio_util::io::ReaderStream;
 use uuid::Uuid;
 
+/// Room for a catalogue CSV plus a zip of product photos.
+const IMPORT_BODY_LIMIT: usize = 100 * 1024 * 1024;
+const EXPORT_BUFFER_SIZE: usize = 64 * 1024;
+
 pub fn get_routes() -> Router<Arc<AppState>> {
     Router::new().nest(
         "/inventory/",
         Router::new()
             .route("/", post(create_product))
-            .route("/{uid}", patch(update_product).delete(delete_product))
-            .route("/{uid}/image", patch(update_product_image)),
+            .route("/low-stock", get(get_low_stock))
+            .route(
+                "/import",
+                post(import_products).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)),
+            )
+            .route("/export", get(export_products))
+            .route("/{uid}", patch(update_product).delete(archive_product))
+            .route("/{uid}/image", patch(update_product_image))
+            .route("/{uid}/adjust", post(adjust_stock))
+            .route("/{uid}/movements", get(get_movements))
+            .route("/{uid}/restore", post(restore_product))
+            .route("/{uid}/purge", delete(purge_product)),
     )
 }
 
 async fn create_product(
     State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
     mut multipart: Multipart,
 ) -> Result<impl IntoResponse, AppError> {
     let pool = &state.postgres_pool;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let req = read_product_form(&mut multipart).await?;
+
     let mut con = pool.get().await?;
+    let new_product = con
+        .transaction(|conn| insert_product(conn, claims.user_uid, &req).scope_boxed())
+        .await?;
+    emit_stock_changed(&state.io, new_product.uuid, new_product.stock);
+
+    Ok((StatusCode::CREATED, Json(new_product)))
+}
 
+/// Reads the `product` JSON and `image` fields of a product form, validates the product
+/// and stores the image. Shared with anything else that creates products.
+pub(crate) async fn read_product_form(multipart: &mut Multipart) -> Result<NewProduct, AppError> {
     let mut product_data: Option<NewProductReq> = None;
     let mut image_data: Option<Bytes> = None;
 
@@ -44,7 +101,7 @@ async fn create_product(
             "product" => {
                 let data = field.text().await.map_err(AppError::from)?;
                 product_data =
-                    Some(serde_json::from_str(&data).map_err(|e| AppError::bad_request(None))?);
+                    Some(serde_json::from_str(&data).map_err(|_| AppError::bad_request(None))?);
             }
             "image" => {
                 image_data = Some(field.bytes().await.map_err(AppError::from)?);
@@ -57,86 +114,477 @@ async fn create_product(
     let mut req: NewProduct = payload.try_into()?;
 
     let image_data = image_data.ok_or_else(|| AppError::bad_request(None))?;
-    let img_path = save_product_image(image_data.iter().as_slice(), &req.title).await?;
-    req.image_path = format!("uploads/products/{}", img_path);
-
-    let new_product = diesel::insert_into(private::products::table)
-        .values(&req)
-        .returning((
-            private::products::uuid,
-            private::products::title,
-            private::products::image_path,
-            private::products::description,
-            private::products::stock,
-            private::products::cost,
-        ))
-        .get_result::<(Uuid, String, String, String, i32, i32)>(&mut con)
-        .await
-        .map_err(AppError::from)?;
-
-    let new_product = Product {
-        uuid: new_product.0,
-        title: new_product.1,
-        image_path: new_product.2,
-        description: new_product.3,
-        stock: new_product.4,
-        cost: new_product.5,
+    req.image = save_product_image(image_data.iter().as_slice()).await?;
+    Ok(req)
+}
+
+/// Imports a catalogue CSV, with an optional zip of images referenced by the `image`
+/// column. Nothing is written unless every row is valid, and `dry_run` only validates.
+async fn import_products(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+    Query(query): Query<ImportQuery>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let mut csv_data: Option<Bytes> = None;
+    let mut images = HashMap::new();
+    while let Some(field) = multipart.next_field().await.map_err(AppError::from)? {
+        match field.name().unwrap_or_default() {
+            "file" => csv_data = Some(field.bytes().await.map_err(AppError::from)?),
+            "images" => images = read_images(&field.bytes().await.map_err(AppError::from)?)?,
+            _ => continue,
+        }
+    }
+    let csv_data = csv_data.ok_or_else(|| AppError::bad_request(None))?;
+
+    let categories = private::categories::table
+        .select((private::categories::name, private::categories::id))
+        .load::<(String, i32)>(&mut con)
+        .await?
+        .into_iter()
+        .map(|(name, id)| (name.to_lowercase(), id))
+        .collect::<HashMap<String, i32>>();
+    let existing = private::products::table
+        .select((private::products::uuid, private::products::stock))
+        .load::<(Uuid, i32)>(&mut con)
+        .await?
+        .into_iter()
+        .collect::<HashMap<Uuid, i32>>();
+
+    let mut report = ImportReport {
+        dry_run: query.dry_run.unwrap_or(false),
+        ..Default::default()
     };
+    let mut rows = vec![];
+    let mut seen = HashSet::new();
+    for (line, row) in read_rows::<ProductCsvRow>(&csv_data) {
+        let row = match row {
+            Ok(row) => row,
+            Err(e) => {
+                report.errors.push(ImportRowError {
+                    line,
+                    errors: vec![e],
+                });
+                continue;
+            }
+        };
 
-    Ok((StatusCode::CREATED, Json(new_product)))
+        let mut errors = vec![];
+        if let Some(uid) = row.uuid {
+            // Stock only moves through the ledger, an old export must not roll it back
+            match existing.get(&uid) {
+                None => errors.push(format!("Unknown product {}", uid)),
+                Some(stock) if *stock != row.stock => errors.push(format!(
+                    "Stock of product {} is now {}, record a stock adjustment to change it",
+                    uid, stock
+                )),
+                Some(_) => {}
+            }
+            if !seen.insert(uid) {
+                errors.push(format!("Product {} appears more than once", uid));
+            }
+        }
+        let category_id = match &row.category {
+            Some(name) => {
+                let id = categories.get(&name.to_lowercase()).copied();
+                if id.is_none() {
+                    errors.push(format!("Unknown category {}", name));
+                }
+                id
+            }
+            None => None,
+        };
+        // Exported rows point at images that are already uploaded
+        let image = row
+            .image
+            .filter(|image| !image.starts_with("uploads/products/"));
+        match &image {
+            Some(image) if !images.contains_key(image) => {
+                errors.push(format!("Image {} is not in the archive", image));
+            }
+            None if row.uuid.is_none() => {
+                errors.push("New products need an image".to_string());
+            }
+            _ => {}
+        }
+
+        let req = NewProductReq {
+            title: row.title,
+            description: row.description,
+            stock: row.stock,
+            cost: row.cost,
+            category_id,
+            tags: split_tags(row.tags),
+            reorder_threshold: row.reorder_threshold,
+        };
+        match req.validate() {
+            Ok(product) if errors.is_empty() => rows.push((row.uuid, product, image)),
+            Ok(_) => report.errors.push(ImportRowError { line, errors }),
+            Err(invalid) => {
+                errors.extend(invalid);
+                report.errors.push(ImportRowError { line, errors });
+            }
+        }
+    }
+
+    if !report.errors.is_empty() {
+        return Ok((StatusCode::BAD_REQUEST, Json(report)));
+    }
+    report.created = rows.iter().filter(|(uid, _, _)| uid.is_none()).count();
+    report.updated = rows.len() - report.created;
+    if report.dry_run {
+        return Ok((StatusCode::OK, Json(report)));
+    }
+
+    let mut saved = vec![];
+    for (_, product, image) in &mut rows {
+        if let Some(image) = image {
+            match save_product_image(&images[image.as_str()]).await {
+                Ok(image) => {
+                    product.image = image.clone();
+                    saved.push(image);
+                }
+                Err(e) => {
+                    remove_product_images(&saved).await;
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    let products = con
+        .transaction(|conn| {
+            async move {
+                let mut products = vec![];
+                for (uid, product, _) in &rows {
+                    let product = match uid {
+                        Some(uid) => {
+                            update_from_import(conn, claims.user_uid, *uid, product).await?
+                        }
+                        None => insert_product(conn, claims.user_uid, product).await?,
+                    };
+                    products.push(product);
+                }
+                Ok::<Vec<Product>, AppError>(products)
+            }
+            .scope_boxed()
+        })
+        .await;
+    let products = match products {
+        Ok(products) => products,
+        Err(e) => {
+            remove_product_images(&saved).await;
+            return Err(e);
+        }
+    };
+    for product in products {
+        emit_stock_changed(&state.io, product.uuid, product.stock);
+    }
+    info!(
+        "Imported {} new and {} updated products",
+        report.created, report.updated
+    );
+
+    Ok((StatusCode::OK, Json(report)))
+}
+
+/// Streams the active catalogue in the same CSV format accepted by the import.
+async fn export_products(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let (writer, reader) = tokio::io::duplex(EXPORT_BUFFER_SIZE);
+    tokio::spawn(write_catalogue(state.postgres_pool.clone(), writer));
+
+    let headers = [
+        (header::CONTENT_TYPE, "text/csv"),
+        (
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"products.csv\"",
+        ),
+    ];
+    Ok((
+        StatusCode::OK,
+        headers,
+        Body::from_stream(ReaderStream::new(reader)),
+    ))
+}
+
+/// Inserts a validated product with its tags and opening stock.
+/// Callers are expected to run this inside a database transaction.
+pub(crate) async fn insert_product(
+    conn: &mut AsyncPgConnection,
+    actor: Uuid,
+    req: &NewProduct,
+) -> Result<Product, AppError> {
+    if let Some(category_id) = req.category_id {
+        ensure_category(conn, category_id).await?;
+    }
+    let uid = diesel::insert_into(private::products::table)
+        .values(req)
+        .returning(private::products::uuid)
+        .get_result::<Uuid>(conn)
+        .await?;
+    set_product_tags(conn, uid, &req.tags).await?;
+    if req.stock > 0 {
+        let movement = NewStockMovement {
+            product_uuid: uid,
+            actor_uuid: Some(actor),
+            kind: StockMovementKind::Restock,
+            delta: req.stock,
+            reason: Some("Initial stock".to_string()),
+            order_uuid: None,
+        };
+        record_movement(conn, movement).await?;
+    }
+    let new_product = lock_product(conn, uid).await?;
+
+    record_audit(
+        conn,
+        actor,
+        AuditAction::ProductCreated,
+        &new_product.uuid.to_string(),
+        None,
+        snapshot(&new_product),
+    )
+    .await?;
+    Ok(new_product)
+}
+
+/// Overwrites a product with an imported row. Stock is left alone, the import only accepts
+/// rows whose stock matches the current level.
+async fn update_from_import(
+    conn: &mut AsyncPgConnection,
+    actor: Uuid,
+    uid: Uuid,
+    req: &NewProduct,
+) -> Result<Product, AppError> {
+    let before = lock_product(conn, uid).await?;
+    let changes = ProductChanges {
+        title: Some(req.title.clone()),
+        description: Some(req.description.clone()),
+        cost: Some(req.cost),
+        category_id: Some(req.category_id),
+        reorder_threshold: Some(req.reorder_threshold),
+    };
+    diesel::update(private::products::table)
+        .filter(private::products::uuid.eq(uid))
+        .set(&changes)
+        .execute(conn)
+        .await?;
+    if !req.image.full.is_empty() {
+        diesel::update(private::products::table)
+            .filter(private::products::uuid.eq(uid))
+            .set(&req.image)
+            .execute(conn)
+            .await?;
+    }
+    set_product_tags(conn, uid, &req.tags).await?;
+    let product = lock_product(conn, uid).await?;
+
+    record_audit(
+        conn,
+        actor,
+        AuditAction::ProductUpdated,
+        &uid.to_string(),
+        snapshot(&before),
+        snapshot(&product),
+    )
+    .await?;
+    Ok(product)
+}
+
+/// Active products at or below their reorder threshold, emptiest first.
+async fn get_low_stock(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let products = find_low_stock(&mut con).await?;
+
+    Ok((StatusCode::OK, Json(products)))
 }
 
 async fn update_product(
     State(state): State<Arc<AppState>>,
     Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
     Json(update_req): Json<UpdateProductReq>,
 ) -> Result<impl IntoResponse, AppError> {
     let pool = &state.postgres_pool;
     let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
 
     let update_product: UpdateProduct = update_req.try_into()?;
 
-    let updated_product = diesel::update(private::products::table)
-        .filter(private::products::uuid.eq(uid))
-        .set(&update_product)
-        .returning((
-            private::products::uuid,
-            private::products::title,
-            private::products::image_path,
-            private::products::description,
-            private::products::stock,
-            private::products::cost,
-        ))
-        .get_result::<(Uuid, String, String, String, i32, i32)>(&mut con)
+    let product = con
+        .transaction(|conn| {
+            async move {
+                let before = lock_product(conn, uid).await?;
+                if let Some(Some(category_id)) = update_product.changes.category_id {
+                    ensure_category(conn, category_id).await?;
+                }
+                if !update_product.changes.is_empty() {
+                    diesel::update(private::products::table)
+                        .filter(private::products::uuid.eq(uid))
+                        .set(&update_product.changes)
+                        .execute(conn)
+                        .await?;
+                }
+                if let Some(tags) = &update_product.tags {
+                    set_product_tags(conn, uid, tags).await?;
+                }
+                let product = lock_product(conn, uid).await?;
+
+                record_audit(
+                    conn,
+                    claims.user_uid,
+                    AuditAction::ProductUpdated,
+                    &uid.to_string(),
+                    snapshot(&before),
+                    snapshot(&product),
+                )
+                .await?;
+                Ok::<Product, AppError>(product)
+            }
+            .scope_boxed()
+        })
+        .await?;
+    emit_stock_changed(&state.io, product.uuid, product.stock);
+
+    Ok((StatusCode::OK, Json(product)))
+}
+
+async fn adjust_stock(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+    Json(payload): Json<StockAdjustmentReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+    let req: StockAdjustment = payload.try_into()?;
+
+    let product = con
+        .transaction(|conn| {
+            async move {
+                let before = lock_product(conn, uid).await?;
+                let movement = NewStockMovement {
+                    product_uuid: uid,
+                    actor_uuid: Some(claims.user_uid),
+                    kind: req.kind,
+                    delta: req.delta,
+                    reason: req.reason.clone(),
+                    order_uuid: None,
+                };
+                let stock = record_movement(conn, movement).await?;
+
+                record_audit(
+                    conn,
+                    claims.user_uid,
+                    AuditAction::ProductStockAdjusted,
+                    &uid.to_string(),
+                    Some(json!({ "stock": before.stock })),
+                    Some(json!({ "stock": stock, "kind": req.kind, "reason": req.reason })),
+                )
+                .await?;
+                lock_product(conn, uid).await
+            }
+            .scope_boxed()
+        })
+        .await?;
+    emit_stock_changed(&state.io, product.uuid, product.stock);
+
+    Ok((StatusCode::OK, Json(product)))
+}
+
+async fn get_movements(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Query(params): Query<MovementQueryParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let req: MovementQuery = params.try_into()?;
+
+    private::products::table
+        .find(uid)
+        .select(private::products::uuid)
+        .first::<Uuid>(&mut con)
         .await
-        .optional()
-        .map_err(AppError::from)?
-        .ok_or_else(|| AppError::not_found())?;
+        .optional()?
+        .ok_or_else(AppError::not_found)?;
 
-    let product = Product {
-        uuid: updated_product.0,
-        title: updated_product.1,
-        image_path: updated_product.2,
-        description: updated_product.3,
-        stock: updated_product.4,
-        cost: updated_product.5,
+    let filtered = || {
+        let mut query = private::stock_movements::table
+            .filter(private::stock_movements::product_uuid.eq(uid))
+            .into_boxed();
+        if let Some(kind) = req.kind {
+            query = query.filter(private::stock_movements::kind.eq(kind));
+        }
+        if let Some(from) = req.from {
+            query = query.filter(private::stock_movements::created_at.ge(from));
+        }
+        if let Some(to) = req.to {
+            query = query.filter(private::stock_movements::created_at.lt(to));
+        }
+        query
     };
 
-    Ok((StatusCode::OK, Json(product)))
+    let total: i64 = filtered().count().get_result(&mut con).await?;
+    let items = filtered()
+        .select(StockMovement::as_select())
+        .order((
+            private::stock_movements::created_at.desc(),
+            private::stock_movements::id.desc(),
+        ))
+        .limit(req.per_page)
+        .offset((req.page - 1) * req.per_page)
+        .load::<StockMovement>(&mut con)
+        .await?;
+
+    let res = Paginated {
+        items,
+        page: req.page,
+        per_page: req.per_page,
+        total,
+    };
+
+    Ok((StatusCode::OK, Json(res)))
 }
 
 async fn update_product_image(
     State(state): State<Arc<AppState>>,
     Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
     mut multipart: Multipart,
 ) -> Result<impl IntoResponse, AppError> {
     let pool = &state.postgres_pool;
     let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
 
-    let product = private::products::table
+    private::products::table
         .filter(private::products::uuid.eq(uid))
-        .select(private::products::title)
-        .first::<String>(&mut con)
+        .select(private::products::uuid)
+        .first::<Uuid>(&mut con)
         .await
         .optional()?
         .ok_or_else(|| AppError::not_found())?;
@@ -151,35 +599,210 @@ async fn update_product_image(
 
     let image_data = image_data.ok_or_else(|| AppError::bad_request(None))?;
 
-    let img_path = save_product_image(&*image_data, &product).await?;
-    let img_path = format!("uploads/products/{}", img_path);
+    let image = save_product_image(&image_data).await?;
 
-    diesel::update(private::products::table)
-        .filter(private::products::uuid.eq(uid))
-        .set(private::products::image_path.eq(&img_path))
-        .execute(&mut con)
-        .await
-        .map_err(AppError::from)?;
+    con.transaction(|conn| {
+        async move {
+            let before = lock_product(conn, uid).await?;
+            diesel::update(private::products::table)
+                .filter(private::products::uuid.eq(uid))
+                .set(&image)
+                .execute(conn)
+                .await?;
+
+            record_audit(
+                conn,
+                claims.user_uid,
+                AuditAction::ProductImageUpdated,
+                &uid.to_string(),
+                Some(json!({ "image_path": before.image_path })),
+                Some(json!({ "image_path": image.full })),
+            )
+            .await
+        }
+        .scope_boxed()
+    })
+    .await?;
 
     Ok((StatusCode::OK, ()))
 }
 
-async fn delete_product(
+/// Hides the product from the catalogue, orders that reference it keep resolving.
+async fn archive_product(
     State(state): State<Arc<AppState>>,
     Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
 ) -> Result<impl IntoResponse, AppError> {
     let pool = &state.postgres_pool;
     let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
 
-    let deleted_count = diesel::delete(private::products::table)
-        .filter(private::products::uuid.eq(uid))
-        .execute(&mut con)
-        .await
-        .map_err(AppError::from)?;
+    con.transaction(|conn| {
+        async move {
+            let before = lock_product(conn, uid).await?;
+            if before.archived_at.is_some() {
+                let errors = vec!["Product is already archived".to_string()];
+                return Err(AppError::bad_request::<ClientErrorMessages>(
+                    DataValidationError { errors }.into(),
+                ));
+            }
+            let archived_at = diesel::update(private::products::table)
+                .filter(private::products::uuid.eq(uid))
+                .set(private::products::archived_at.eq(diesel::dsl::now))
+                .returning(private::products::archived_at)
+                .get_result::<Option<NaiveDateTime>>(conn)
+                .await?;
 
-    if deleted_count == 0 {
-        return Err(AppError::not_found());
-    }
+            record_audit(
+                conn,
+                claims.user_uid,
+                AuditAction::ProductArchived,
+                &uid.to_string(),
+                Some(json!({ "archived_at": before.archived_at })),
+                Some(json!({ "archived_at": archived_at })),
+            )
+            .await
+        }
+        .scope_boxed()
+    })
+    .await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
+
+async fn restore_product(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let product = con
+        .transaction(|conn| {
+            async move {
+                let before = lock_product(conn, uid).await?;
+                if before.archived_at.is_none() {
+                    let errors = vec!["Product is not archived".to_string()];
+                    return Err(AppError::bad_request::<ClientErrorMessages>(
+                        DataValidationError { errors }.into(),
+                    ));
+                }
+                diesel::update(private::products::table)
+                    .filter(private::products::uuid.eq(uid))
+                    .set(private::products::archived_at.eq(None::<NaiveDateTime>))
+                    .execute(conn)
+                    .await?;
+                let product = lock_product(conn, uid).await?;
+
+                record_audit(
+                    conn,
+                    claims.user_uid,
+                    AuditAction::ProductRestored,
+                    &uid.to_string(),
+                    Some(json!({ "archived_at": before.archived_at })),
+                    Some(json!({ "archived_at": product.archived_at })),
+                )
+                .await?;
+                Ok::<Product, AppError>(product)
+            }
+            .scope_boxed()
+        })
+        .await?;
+    emit_stock_changed(&state.io, product.uuid, product.stock);
+
+    Ok((StatusCode::OK, Json(product)))
+}
+
+/// Permanently removes an archived product that never appeared in an order.
+async fn purge_product(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    con.transaction(|conn| {
+        async move {
+            let before = lock_product(conn, uid).await?;
+            let mut errors = vec![];
+            if before.archived_at.is_none() {
+                errors.push("Product must be archived before it is purged".to_string());
+            }
+            let orders: i64 = private::order_items::table
+                .filter(private::order_items::product_uuid.eq(uid))
+                .count()
+                .get_result(conn)
+                .await?;
+            if orders > 0 {
+                errors.push("Product appears in orders and can only be archived".to_string());
+            }
+            if !errors.is_empty() {
+                return Err(AppError::bad_request::<ClientErrorMessages>(
+                    DataValidationError { errors }.into(),
+                ));
+            }
+
+            diesel::delete(private::products::table)
+                .filter(private::products::uuid.eq(uid))
+                .execute(conn)
+                .await?;
+
+            record_audit(
+                conn,
+                claims.user_uid,
+                AuditAction::ProductPurged,
+                &uid.to_string(),
+                snapshot(&before),
+                None,
+            )
+            .await
+        }
+        .scope_boxed()
+    })
+    .await?;
+
+    let referenced = private::products::table
+        .select((
+            private::products::image_path,
+            private::products::card_path,
+            private::products::thumbnail_path,
+        ))
+        .load::<(String, String, String)>(&mut con)
+        .await?
+        .into_iter()
+        .flat_map(|(full, card, thumbnail)| [full, card, thumbnail])
+        .collect::<Vec<String>>();
+    remove_orphaned_product_images(&referenced).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn lock_product(conn: &mut AsyncPgConnection, uid: Uuid) -> Result<Product, AppError> {
+    let record = private::products::table
+        .filter(private::products::uuid.eq(uid))
+        .select(ProductRecord::as_select())
+        .for_update()
+        .first::<ProductRecord>(conn)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)?;
+    let tags = load_tags(conn, &[uid]).await?.remove(&uid);
+
+    Ok(Product::new(record, tags.unwrap_or_default()))
+}
@@ -1,16 +1,22 @@
+use crate::backend::sessions::revoke_user_sessions;
 use crate::helper::hash_password;
 use crate::models::user::User;
+use crate::models::wallet::{Transaction, Wallet};
 use crate::paseto::AuthTokenClaims;
-use crate::req_res::me::{PasswordChangeReq, PasswordChangeValidated};
-use crate::req_res::AppError;
+use crate::req_res::me::{
+    ExpiringPoints, PasswordChangeReq, PasswordChangeValidated, TransactionQuery,
+    TransactionQueryParams, WalletRes,
+};
+use crate::req_res::{AppError, Paginated};
 use crate::schema::private;
 use crate::schema::private::users::uuid as SqlUuid;
 use crate::AppState;
-use axum::extract::State;
+use axum::extract::{Query, State};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::routing::{get, post};
 use axum::{Extension, Json, Router};
+use chrono::{NaiveDateTime, Utc};
 use diesel::associations::HasTable;
 use diesel::prelude::*;
 use diesel::result::Error;
@@ -25,6 +31,8 @@ pub fn get_scope() -> Router<Arc<AppState>> {
         .route("/change-required", get(check_pw_change))
         .route("/settings", get(settings))
         .route("/settings/change-password", post(process_password_change))
+        .route("/wallet", get(get_wallet))
+        .route("/transactions", get(get_transactions))
 }
 
 async fn settings(State(_state): State<Arc<AppState>>) -> Result<impl IntoResponse, AppError> {
@@ -81,6 +89,101 @@ async fn process_password_change(
         ))
         .execute(&mut con)
         .await?;
+    revoke_user_sessions(&state.redis_client, claims.user_uid).await?;
 
     Ok((StatusCode::OK, ()))
 }
+
+async fn get_wallet(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let wallet = private::wallets::table
+        .filter(private::wallets::user_uuid.eq(claims.user_uid))
+        .select(Wallet::as_select())
+        .first::<Wallet>(&mut con)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)?;
+
+    let expiring = private::credit_lots::table
+        .filter(private::credit_lots::wallet_id.eq(wallet.id))
+        .filter(private::credit_lots::remaining.gt(0))
+        .filter(private::credit_lots::expires_at.gt(Utc::now().naive_utc()))
+        .group_by(private::credit_lots::expires_at)
+        .select((
+            diesel::dsl::sum(private::credit_lots::remaining),
+            private::credit_lots::expires_at,
+        ))
+        .order(private::credit_lots::expires_at.asc())
+        .load::<(Option<i64>, Option<NaiveDateTime>)>(&mut con)
+        .await?
+        .into_iter()
+        .filter_map(|(amount, expires_at)| {
+            Some(ExpiringPoints {
+                amount: amount?,
+                expires_at: expires_at?,
+            })
+        })
+        .collect::<Vec<ExpiringPoints>>();
+
+    Ok((StatusCode::OK, Json(WalletRes { wallet, expiring })))
+}
+
+async fn get_transactions(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+    Query(params): Query<TransactionQueryParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+    let req: TransactionQuery = params.try_into()?;
+
+    let filtered = || {
+        let mut query = private::transactions::table
+            .inner_join(private::wallets::table)
+            .filter(private::wallets::user_uuid.eq(claims.user_uid))
+            .into_boxed();
+        if let Some(from) = req.from {
+            query = query.filter(private::transactions::created_at.ge(from));
+        }
+        if let Some(to) = req.to {
+            query = query.filter(private::transactions::created_at.lt(to));
+        }
+        query
+    };
+
+    let total: i64 = filtered().count().get_result(&mut con).await?;
+    let items = filtered()
+        .select(Transaction::as_select())
+        .order((
+            private::transactions::created_at.desc(),
+            private::transactions::id.desc(),
+        ))
+        .limit(req.per_page)
+        .offset((req.page - 1) * req.per_page)
+        .load::<Transaction>(&mut con)
+        .await?;
+
+    let res = Paginated {
+        items,
+        page: req.page,
+        per_page: req.per_page,
+        total,
+    };
+
+    Ok((StatusCode::OK, Json(res)))
+}
@@ -1,7 +1,14 @@
+pub mod auctions;
+pub mod audit;
 pub mod auth;
+pub mod catalogue;
+pub mod credit_rules;
 pub mod inventory;
 pub mod me;
 pub mod orders;
+pub mod policies;
+pub mod product_requests;
 pub mod products;
 pub mod public;
+pub mod tasks;
 pub mod users;
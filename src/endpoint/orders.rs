@@ -1 +1,497 @@
+use crate::backend::audit::{record_audit, snapshot};
+use crate::backend::stock::record_movement;
+use crate::backend::wallet::{debit_wallet, refund_order};
+use crate::models::audit::AuditAction;
+use crate::models::orders::{Order, OrderItem, OrderStatus};
+use crate::models::stock::{NewStockMovement, StockMovementKind};
+use crate::models::wallet::Wallet;
+use crate::paseto::AuthTokenClaims;
+use crate::req_res::orders::{
+    NewOrderReq, NewOrderValidated, OrderItemRes, OrderQueryParams, OrderRes,
+};
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use crate::schema::private;
+use crate::websocket::{emit_balance_changed, emit_order_status_changed, emit_stock_changed};
+use crate::AppState;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use log::error;
+use pasetors::claims::Claims;
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
 
+pub fn get_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .nest(
+            "/orders/",
+            Router::new()
+                .route("/", get(get_my_orders).post(place_order))
+                .route("/{uid}", get(get_my_order)),
+        )
+        .nest(
+            "/inventory/orders/",
+            Router::new()
+                .route("/", get(get_orders))
+                .route("/{uid}", get(get_order))
+                .route("/{uid}/approve", post(approve_order))
+                .route("/{uid}/fulfil", post(fulfil_order))
+                .route("/{uid}/cancel", post(cancel_order)),
+        )
+}
+
+async fn place_order(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+    Json(payload): Json<NewOrderReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+    let req: NewOrderValidated = payload.try_into()?;
+
+    let (order, stock_changes, wallet) = con
+        .transaction(|conn| {
+            async move {
+                let mut total_cost: i32 = 0;
+                let mut items = vec![];
+                let mut stock_changes = vec![];
+
+                for item in &req.items {
+                    let (title, stock, cost) = private::products::table
+                        .filter(private::products::uuid.eq(item.product_uuid))
+                        .filter(private::products::archived_at.is_null())
+                        .select((
+                            private::products::title,
+                            private::products::stock,
+                            private::products::cost,
+                        ))
+                        .for_update()
+                        .first::<(String, i32, i32)>(conn)
+                        .await
+                        .optional()?
+                        .ok_or_else(|| {
+                            let errors = vec![format!("Unknown product {}", item.product_uuid)];
+                            AppError::bad_request::<ClientErrorMessages>(
+                                DataValidationError { errors }.into(),
+                            )
+                        })?;
+
+                    if stock < item.quantity {
+                        let errors = vec![format!("Insufficient stock for {}", title)];
+                        return Err(AppError::bad_request::<ClientErrorMessages>(
+                            DataValidationError { errors }.into(),
+                        ));
+                    }
+
+                    total_cost = cost
+                        .checked_mul(item.quantity)
+                        .and_then(|line_cost| total_cost.checked_add(line_cost))
+                        .ok_or_else(|| AppError::bad_request(None))?;
+
+                    items.push(OrderItemRes {
+                        product_uuid: item.product_uuid,
+                        title,
+                        quantity: item.quantity,
+                        unit_cost: cost,
+                    });
+                }
+
+                let order = diesel::insert_into(private::orders::table)
+                    .values((
+                        private::orders::user_uuid.eq(claims.user_uid),
+                        private::orders::total_cost.eq(total_cost),
+                    ))
+                    .returning(Order::as_returning())
+                    .get_result::<Order>(conn)
+                    .await?;
+
+                let rows = items
+                    .iter()
+                    .map(|item| {
+                        (
+                            private::order_items::order_uuid.eq(order.uuid),
+                            private::order_items::product_uuid.eq(item.product_uuid),
+                            private::order_items::quantity.eq(item.quantity),
+                            private::order_items::unit_cost.eq(item.unit_cost),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                diesel::insert_into(private::order_items::table)
+                    .values(rows)
+                    .execute(conn)
+                    .await?;
+
+                for item in &items {
+                    let movement = NewStockMovement {
+                        product_uuid: item.product_uuid,
+                        actor_uuid: Some(claims.user_uid),
+                        kind: StockMovementKind::Redemption,
+                        delta: -item.quantity,
+                        reason: None,
+                        order_uuid: Some(order.uuid),
+                    };
+                    let stock = record_movement(conn, movement).await?;
+                    stock_changes.push((item.product_uuid, stock));
+                }
+
+                let wallet = debit_wallet(
+                    conn,
+                    claims.user_uid,
+                    total_cost,
+                    &format!("Order {}", order.uuid),
+                    Some(order.uuid),
+                )
+                .await?;
+
+                Ok::<(OrderRes, Vec<(Uuid, i32)>, Wallet), AppError>((
+                    (order, items).into(),
+                    stock_changes,
+                    wallet,
+                ))
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    for (product_uuid, stock) in stock_changes {
+        emit_stock_changed(&state.io, product_uuid, stock);
+    }
+    emit_balance_changed(&state.io, wallet.user_uuid, wallet.balance);
+    emit_order_status_changed(&state.io, order.uuid, order.user_uuid, order.status);
+
+    Ok((StatusCode::CREATED, Json(order)))
+}
+
+async fn get_my_orders(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+    Query(params): Query<OrderQueryParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let mut query = private::orders::table
+        .filter(private::orders::user_uuid.eq(claims.user_uid))
+        .select(Order::as_select())
+        .order(private::orders::created_at.desc())
+        .into_boxed();
+
+    if let Some(status) = params.status {
+        query = query.filter(private::orders::status.eq(status));
+    }
+
+    let orders = query.load::<Order>(&mut con).await?;
+    let res = with_items(&mut con, orders).await?;
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+async fn get_my_order(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let order = private::orders::table
+        .filter(private::orders::uuid.eq(uid))
+        .filter(private::orders::user_uuid.eq(claims.user_uid))
+        .select(Order::as_select())
+        .first::<Order>(&mut con)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)?;
+
+    let res = with_items(&mut con, vec![order]).await?.remove(0);
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+async fn get_orders(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<OrderQueryParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+
+    let mut query = private::orders::table
+        .select(Order::as_select())
+        .order(private::orders::created_at.desc())
+        .into_boxed();
+
+    if let Some(status) = params.status {
+        query = query.filter(private::orders::status.eq(status));
+    }
+
+    let orders = query.load::<Order>(&mut con).await?;
+    let res = with_items(&mut con, orders).await?;
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+async fn get_order(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+
+    let order = private::orders::table
+        .find(uid)
+        .select(Order::as_select())
+        .first::<Order>(&mut con)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)?;
+
+    let res = with_items(&mut con, vec![order]).await?.remove(0);
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+async fn approve_order(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let order = con
+        .transaction(|conn| {
+            async move {
+                transition_order(
+                    conn,
+                    claims.user_uid,
+                    uid,
+                    &[OrderStatus::Pending],
+                    OrderStatus::Approved,
+                )
+                .await
+            }
+            .scope_boxed()
+        })
+        .await?;
+    emit_order_status_changed(&state.io, order.uuid, order.user_uuid, order.status);
+    let res = with_items(&mut con, vec![order]).await?.remove(0);
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+async fn fulfil_order(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let order = con
+        .transaction(|conn| {
+            async move {
+                transition_order(
+                    conn,
+                    claims.user_uid,
+                    uid,
+                    &[OrderStatus::Approved],
+                    OrderStatus::Fulfilled,
+                )
+                .await
+            }
+            .scope_boxed()
+        })
+        .await?;
+    emit_order_status_changed(&state.io, order.uuid, order.user_uuid, order.status);
+    let res = with_items(&mut con, vec![order]).await?.remove(0);
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+async fn cancel_order(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let (order, stock_changes, wallet) = con
+        .transaction(|conn| {
+            async move {
+                let order = transition_order(
+                    conn,
+                    claims.user_uid,
+                    uid,
+                    &[OrderStatus::Pending, OrderStatus::Approved],
+                    OrderStatus::Cancelled,
+                )
+                .await?;
+
+                let items = private::order_items::table
+                    .filter(private::order_items::order_uuid.eq(order.uuid))
+                    .select(OrderItem::as_select())
+                    .load::<OrderItem>(conn)
+                    .await?;
+
+                let mut stock_changes = vec![];
+                for item in items {
+                    let movement = NewStockMovement {
+                        product_uuid: item.product_uuid,
+                        actor_uuid: Some(claims.user_uid),
+                        kind: StockMovementKind::Cancellation,
+                        delta: item.quantity,
+                        reason: None,
+                        order_uuid: Some(order.uuid),
+                    };
+                    let stock = record_movement(conn, movement).await?;
+                    stock_changes.push((item.product_uuid, stock));
+                }
+
+                let wallet = refund_order(
+                    conn,
+                    order.user_uuid,
+                    order.uuid,
+                    order.total_cost,
+                    &format!("Refund for order {}", order.uuid),
+                )
+                .await?;
+
+                Ok::<(Order, Vec<(Uuid, i32)>, Wallet), AppError>((order, stock_changes, wallet))
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    for (product_uuid, stock) in stock_changes {
+        emit_stock_changed(&state.io, product_uuid, stock);
+    }
+    emit_balance_changed(&state.io, wallet.user_uuid, wallet.balance);
+    emit_order_status_changed(&state.io, order.uuid, order.user_uuid, order.status);
+    let res = with_items(&mut con, vec![order]).await?.remove(0);
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+/// Moves an order to `to` if it is currently in one of the `from` states and
+/// records the change against the acting staff member.
+async fn transition_order(
+    conn: &mut AsyncPgConnection,
+    actor: Uuid,
+    uid: Uuid,
+    from: &[OrderStatus],
+    to: OrderStatus,
+) -> Result<Order, AppError> {
+    let order = private::orders::table
+        .find(uid)
+        .select(Order::as_select())
+        .for_update()
+        .first::<Order>(conn)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)?;
+
+    if !from.contains(&order.status) {
+        let errors = vec![format!(
+            "Order is {:?} and cannot be changed to {:?}",
+            order.status, to
+        )];
+        return Err(AppError::bad_request::<ClientErrorMessages>(
+            DataValidationError { errors }.into(),
+        ));
+    }
+
+    let updated = diesel::update(private::orders::table.find(uid))
+        .set(private::orders::status.eq(to))
+        .returning(Order::as_returning())
+        .get_result::<Order>(conn)
+        .await?;
+
+    let action = match to {
+        OrderStatus::Fulfilled => AuditAction::OrderFulfilled,
+        OrderStatus::Cancelled => AuditAction::OrderCancelled,
+        _ => AuditAction::OrderApproved,
+    };
+    record_audit(
+        conn,
+        actor,
+        action,
+        &uid.to_string(),
+        snapshot(&order),
+        snapshot(&updated),
+    )
+    .await?;
+
+    Ok(updated)
+}
+
+async fn with_items(
+    conn: &mut AsyncPgConnection,
+    orders: Vec<Order>,
+) -> Result<Vec<OrderRes>, AppError> {
+    let order_uids = orders.iter().map(|o| o.uuid).collect::<Vec<Uuid>>();
+    let rows = private::order_items::table
+        .inner_join(private::products::table)
+        .filter(private::order_items::order_uuid.eq_any(&order_uids))
+        .select((OrderItem::as_select(), private::products::title))
+        .order(private::order_items::id.asc())
+        .load::<(OrderItem, String)>(conn)
+        .await?;
+
+    let mut items_by_order: HashMap<Uuid, Vec<OrderItemRes>> = HashMap::new();
+    for (item, title) in rows {
+        items_by_order
+            .entry(item.order_uuid)
+            .or_default()
+            .push(OrderItemRes {
+                product_uuid: item.product_uuid,
+                title,
+                quantity: item.quantity,
+                unit_cost: item.unit_cost,
+            });
+    }
+
+    Ok(orders
+        .into_iter()
+        .map(|order| {
+            let items = items_by_order.remove(&order.uuid).unwrap_or_default();
+            (order, items).into()
+        })
+        .collect())
+}
@@ -0,0 +1,218 @@
+use crate::backend::audit::{record_audit, snapshot};
+use crate::backend::policy::{delete_rule, insert_rule};
+use crate::models::audit::AuditAction;
+use crate::paseto::AuthTokenClaims;
+use crate::req_res::policies::{PolicyRule, PolicyRuleReq};
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use crate::AppState;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use axum_casbin::casbin::{CachedApi, CachedEnforcer, CoreApi, MgmtApi};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::AsyncConnection;
+use log::{error, info};
+use pasetors::claims::Claims;
+use std::sync::Arc;
+
+/// The role and route that must always stay reachable, so policies can be repaired.
+const ADMIN_SUBJECT: &str = "Admin";
+const ADMIN_POLICY_PATH: &str = "/admin/policies";
+
+pub fn get_routes() -> Router<Arc<AppState>> {
+    Router::new().nest(
+        "/admin/policies/",
+        Router::new()
+            .route(
+                "/",
+                get(get_policies).post(add_policy).delete(remove_policy),
+            )
+            .route("/reload", post(reload_policies)),
+    )
+}
+
+async fn get_policies(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse, AppError> {
+    let enforcer = state.enforcer.read().await;
+
+    let mut rules = enforcer
+        .get_named_policy("p")
+        .into_iter()
+        .map(|rule| PolicyRule {
+            ptype: "p".to_string(),
+            rule,
+        })
+        .collect::<Vec<PolicyRule>>();
+    for ptype in ["g", "g2", "g3"] {
+        rules.extend(
+            enforcer
+                .get_named_grouping_policy(ptype)
+                .into_iter()
+                .map(|rule| PolicyRule {
+                    ptype: ptype.to_string(),
+                    rule,
+                }),
+        );
+    }
+
+    Ok((StatusCode::OK, Json(rules)))
+}
+
+async fn add_policy(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+    Json(payload): Json<PolicyRuleReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+    let req: PolicyRule = payload.try_into()?;
+    let mut con = pool.get().await?;
+
+    let mut enforcer = state.enforcer.write().await;
+    if !set_in_memory(&mut enforcer, &req, true).await? {
+        let errors = vec!["Rule already exists".to_string()];
+        return Err(AppError::bad_request::<ClientErrorMessages>(
+            DataValidationError { errors }.into(),
+        ));
+    }
+
+    let rule = &req;
+    let saved = con
+        .transaction(|conn| {
+            async move {
+                insert_rule(conn, &rule.ptype, rule.rule.clone()).await?;
+                record_audit(
+                    conn,
+                    claims.user_uid,
+                    AuditAction::PolicyAdded,
+                    &rule_id(rule),
+                    None,
+                    snapshot(rule),
+                )
+                .await
+            }
+            .scope_boxed()
+        })
+        .await;
+    if let Err(e) = saved {
+        set_in_memory(&mut enforcer, &req, false).await?;
+        return Err(e);
+    }
+
+    Ok((StatusCode::CREATED, Json(rule.clone())))
+}
+
+async fn remove_policy(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+    Json(payload): Json<PolicyRuleReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+    let req: PolicyRule = payload.try_into()?;
+    let mut con = pool.get().await?;
+
+    let mut enforcer = state.enforcer.write().await;
+    if !set_in_memory(&mut enforcer, &req, false).await? {
+        return Err(AppError::not_found());
+    }
+
+    // Without a way back into /admin the rules could only be repaired in the database
+    let admin_locked_out = ["POST", "DELETE"].into_iter().any(|act| {
+        !enforcer
+            .enforce((ADMIN_SUBJECT, ADMIN_POLICY_PATH, act))
+            .unwrap_or(false)
+    });
+    if admin_locked_out {
+        set_in_memory(&mut enforcer, &req, true).await?;
+        let errors = vec!["Removing this rule would lock administrators out of /admin".to_string()];
+        return Err(AppError::bad_request::<ClientErrorMessages>(
+            DataValidationError { errors }.into(),
+        ));
+    }
+
+    let rule = &req;
+    let saved = con
+        .transaction(|conn| {
+            async move {
+                delete_rule(conn, &rule.ptype, rule.rule.clone()).await?;
+                record_audit(
+                    conn,
+                    claims.user_uid,
+                    AuditAction::PolicyRemoved,
+                    &rule_id(rule),
+                    snapshot(rule),
+                    None,
+                )
+                .await
+            }
+            .scope_boxed()
+        })
+        .await;
+    if let Err(e) = saved {
+        set_in_memory(&mut enforcer, &req, true).await?;
+        return Err(e);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Adds or removes the rule in the loaded model only, the caller writes it to the
+/// database together with its audit entry. Returns whether the model changed.
+async fn set_in_memory(
+    enforcer: &mut CachedEnforcer,
+    rule: &PolicyRule,
+    present: bool,
+) -> Result<bool, AppError> {
+    enforcer.enable_auto_save(false);
+    let changed = match (rule.is_grouping(), present) {
+        (true, true) => {
+            enforcer
+                .add_named_grouping_policy(&rule.ptype, rule.rule.clone())
+                .await
+        }
+        (true, false) => {
+            enforcer
+                .remove_named_grouping_policy(&rule.ptype, rule.rule.clone())
+                .await
+        }
+        (false, true) => {
+            enforcer
+                .add_named_policy(&rule.ptype, rule.rule.clone())
+                .await
+        }
+        (false, false) => {
+            enforcer
+                .remove_named_policy(&rule.ptype, rule.rule.clone())
+                .await
+        }
+    };
+    enforcer.enable_auto_save(true);
+    enforcer.get_mut_cache().clear();
+    Ok(changed?)
+}
+
+/// Reloads every rule from the database, picking up changes made outside the API.
+async fn reload_policies(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut enforcer = state.enforcer.write().await;
+    enforcer.load_policy().await?;
+    enforcer.get_mut_cache().clear();
+    info!("Authorization policies reloaded");
+
+    Ok((StatusCode::OK, ()))
+}
+
+fn rule_id(rule: &PolicyRule) -> String {
+    format!("{}, {}", rule.ptype, rule.rule.join(", "))
+}
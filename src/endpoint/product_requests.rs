@@ -0,0 +1,501 @@
+use crate::backend::audit::{record_audit, snapshot};
+use crate::endpoint::inventory::{insert_product, read_product_form};
+use crate::models::audit::AuditAction;
+use crate::models::product_requests::{ProductRequest, ProductRequestStatus};
+use crate::models::user::User;
+use crate::notifications::queue::notify_user;
+use crate::notifications::templates::Template;
+use crate::paseto::AuthTokenClaims;
+use crate::req_res::product_requests::{
+    NewProductRequest, NewProductRequestReq, ProductRequestQueryParams, ProductRequestRes,
+    RejectProductRequest, RejectProductRequestReq, ResidentProductRequestRes,
+};
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use crate::schema::private;
+use crate::websocket::emit_stock_changed;
+use crate::AppState;
+use axum::extract::{Multipart, Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use log::error;
+use pasetors::claims::Claims;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Requests residents can still vote on.
+const VOTABLE: [ProductRequestStatus; 2] =
+    [ProductRequestStatus::Open, ProductRequestStatus::Approved];
+
+pub fn get_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .nest(
+            "/product-requests/",
+            Router::new()
+                .route("/", get(get_product_requests).post(create_product_request))
+                .route("/mine", get(get_my_product_requests))
+                .route("/{uid}/vote", post(vote).delete(unvote)),
+        )
+        .nest(
+            "/inventory/product-requests/",
+            Router::new()
+                .route("/", get(get_all_product_requests))
+                .route("/{uid}/approve", post(approve_product_request))
+                .route("/{uid}/reject", post(reject_product_request))
+                .route("/{uid}/convert", post(convert_product_request)),
+        )
+}
+
+/// Requests that are still being considered, most wanted first.
+async fn get_product_requests(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let requests = private::product_requests::table
+        .filter(private::product_requests::status.eq_any(VOTABLE))
+        .select(ProductRequest::as_select())
+        .load::<ProductRequest>(&mut con)
+        .await?;
+    let mut res = for_resident(&mut con, requests, claims.user_uid).await?;
+    res.sort_by(|a, b| b.votes.cmp(&a.votes).then(b.created_at.cmp(&a.created_at)));
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+async fn get_my_product_requests(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let requests = private::product_requests::table
+        .filter(private::product_requests::user_uuid.eq(claims.user_uid))
+        .select(ProductRequest::as_select())
+        .order(private::product_requests::created_at.desc())
+        .load::<ProductRequest>(&mut con)
+        .await?;
+    let res = for_resident(&mut con, requests, claims.user_uid).await?;
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+async fn create_product_request(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+    Json(payload): Json<NewProductRequestReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+    let req: NewProductRequest = payload.try_into()?;
+
+    let request = diesel::insert_into(private::product_requests::table)
+        .values((
+            &req,
+            private::product_requests::user_uuid.eq(claims.user_uid),
+        ))
+        .returning(ProductRequest::as_returning())
+        .get_result::<ProductRequest>(&mut con)
+        .await?;
+    let res = for_resident(&mut con, vec![request], claims.user_uid)
+        .await?
+        .remove(0);
+
+    Ok((StatusCode::CREATED, Json(res)))
+}
+
+async fn vote(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let request = find_request(&mut con, uid).await?;
+    if !VOTABLE.contains(&request.status) {
+        let errors = vec!["This request is closed for voting".to_string()];
+        return Err(AppError::bad_request::<ClientErrorMessages>(
+            DataValidationError { errors }.into(),
+        ));
+    }
+
+    diesel::insert_into(private::product_request_votes::table)
+        .values((
+            private::product_request_votes::request_uuid.eq(uid),
+            private::product_request_votes::user_uuid.eq(claims.user_uid),
+        ))
+        .on_conflict_do_nothing()
+        .execute(&mut con)
+        .await?;
+    let res = for_resident(&mut con, vec![request], claims.user_uid)
+        .await?
+        .remove(0);
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+async fn unvote(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let request = find_request(&mut con, uid).await?;
+    diesel::delete(private::product_request_votes::table.find((uid, claims.user_uid)))
+        .execute(&mut con)
+        .await?;
+    let res = for_resident(&mut con, vec![request], claims.user_uid)
+        .await?
+        .remove(0);
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+async fn get_all_product_requests(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+    Query(params): Query<ProductRequestQueryParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let mut query = private::product_requests::table
+        .select(ProductRequest::as_select())
+        .order(private::product_requests::created_at.desc())
+        .into_boxed();
+
+    if let Some(status) = params.status {
+        query = query.filter(private::product_requests::status.eq(status));
+    }
+
+    let requests = query.load::<ProductRequest>(&mut con).await?;
+    let res = with_votes(&mut con, requests, claims.user_uid).await?;
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+async fn approve_product_request(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let request = con
+        .transaction(|conn| {
+            async move {
+                transition_request(
+                    conn,
+                    claims.user_uid,
+                    uid,
+                    &[ProductRequestStatus::Open],
+                    ProductRequestStatus::Approved,
+                    None,
+                    None,
+                )
+                .await
+            }
+            .scope_boxed()
+        })
+        .await?;
+    notify_requester(&state, &mut con, &request).await;
+    let res = with_votes(&mut con, vec![request], claims.user_uid)
+        .await?
+        .remove(0);
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+async fn reject_product_request(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+    Json(payload): Json<RejectProductRequestReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+    let req: RejectProductRequest = payload.try_into()?;
+
+    let request = con
+        .transaction(|conn| {
+            async move {
+                transition_request(
+                    conn,
+                    claims.user_uid,
+                    uid,
+                    &VOTABLE,
+                    ProductRequestStatus::Rejected,
+                    Some(req.reason),
+                    None,
+                )
+                .await
+            }
+            .scope_boxed()
+        })
+        .await?;
+    notify_requester(&state, &mut con, &request).await;
+    let res = with_votes(&mut con, vec![request], claims.user_uid)
+        .await?
+        .remove(0);
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+/// Creates a catalogue product from the same form as `POST /inventory/` and marks the
+/// request as fulfilled by it.
+async fn convert_product_request(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let request = find_request(&mut con, uid).await?;
+    if !VOTABLE.contains(&request.status) {
+        return Err(status_error(
+            request.status,
+            ProductRequestStatus::Fulfilled,
+        ));
+    }
+    let req = read_product_form(&mut multipart).await?;
+
+    let (request, product) = con
+        .transaction(|conn| {
+            async move {
+                let product = insert_product(conn, claims.user_uid, &req).await?;
+                let request = transition_request(
+                    conn,
+                    claims.user_uid,
+                    uid,
+                    &VOTABLE,
+                    ProductRequestStatus::Fulfilled,
+                    None,
+                    Some(product.uuid),
+                )
+                .await?;
+                Ok::<_, AppError>((request, product))
+            }
+            .scope_boxed()
+        })
+        .await?;
+    emit_stock_changed(&state.io, product.uuid, product.stock);
+    notify_requester(&state, &mut con, &request).await;
+    let res = with_votes(&mut con, vec![request], claims.user_uid)
+        .await?
+        .remove(0);
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+/// Moves a request to `to` if it is currently in one of the `from` states and
+/// records the change against the acting staff member.
+async fn transition_request(
+    conn: &mut AsyncPgConnection,
+    actor: Uuid,
+    uid: Uuid,
+    from: &[ProductRequestStatus],
+    to: ProductRequestStatus,
+    reason: Option<String>,
+    product_uuid: Option<Uuid>,
+) -> Result<ProductRequest, AppError> {
+    let request = private::product_requests::table
+        .find(uid)
+        .select(ProductRequest::as_select())
+        .for_update()
+        .first::<ProductRequest>(conn)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)?;
+
+    if !from.contains(&request.status) {
+        return Err(status_error(request.status, to));
+    }
+
+    let updated = diesel::update(private::product_requests::table.find(uid))
+        .set((
+            private::product_requests::status.eq(to),
+            private::product_requests::review_reason.eq(reason),
+            private::product_requests::reviewed_by.eq(actor),
+            private::product_requests::product_uuid.eq(product_uuid),
+        ))
+        .returning(ProductRequest::as_returning())
+        .get_result::<ProductRequest>(conn)
+        .await?;
+
+    let action = match to {
+        ProductRequestStatus::Rejected => AuditAction::ProductRequestRejected,
+        ProductRequestStatus::Fulfilled => AuditAction::ProductRequestFulfilled,
+        _ => AuditAction::ProductRequestApproved,
+    };
+    record_audit(
+        conn,
+        actor,
+        action,
+        &uid.to_string(),
+        snapshot(&request),
+        snapshot(&updated),
+    )
+    .await?;
+
+    Ok(updated)
+}
+
+/// Tells the requester about a review. The review is already committed by then, so a
+/// failure is only logged rather than turned into an error response.
+async fn notify_requester(
+    state: &AppState,
+    conn: &mut AsyncPgConnection,
+    request: &ProductRequest,
+) {
+    if let Err(e) = queue_review_message(state, conn, request).await {
+        error!(
+            "Unable to queue review message for request {}: {:?}",
+            request.uuid, e
+        );
+    }
+}
+
+async fn queue_review_message(
+    state: &AppState,
+    conn: &mut AsyncPgConnection,
+    request: &ProductRequest,
+) -> Result<(), AppError> {
+    let user = private::users::table
+        .find(request.user_uuid)
+        .select(User::as_select())
+        .first::<User>(conn)
+        .await?;
+    let template = Template::ProductRequestReviewed {
+        name: &user.name,
+        title: &request.title,
+        status: request.status,
+        reason: request.review_reason.as_deref(),
+    };
+    notify_user(&state.redis_client, &user, &template).await
+}
+
+async fn find_request(conn: &mut AsyncPgConnection, uid: Uuid) -> Result<ProductRequest, AppError> {
+    private::product_requests::table
+        .find(uid)
+        .select(ProductRequest::as_select())
+        .first::<ProductRequest>(conn)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)
+}
+
+async fn with_votes(
+    conn: &mut AsyncPgConnection,
+    requests: Vec<ProductRequest>,
+    user: Uuid,
+) -> Result<Vec<ProductRequestRes>, AppError> {
+    let request_uids = requests.iter().map(|r| r.uuid).collect::<Vec<Uuid>>();
+    let votes = private::product_request_votes::table
+        .filter(private::product_request_votes::request_uuid.eq_any(&request_uids))
+        .group_by(private::product_request_votes::request_uuid)
+        .select((
+            private::product_request_votes::request_uuid,
+            diesel::dsl::count_star(),
+        ))
+        .load::<(Uuid, i64)>(conn)
+        .await?
+        .into_iter()
+        .collect::<HashMap<Uuid, i64>>();
+    let voted = private::product_request_votes::table
+        .filter(private::product_request_votes::request_uuid.eq_any(&request_uids))
+        .filter(private::product_request_votes::user_uuid.eq(user))
+        .select(private::product_request_votes::request_uuid)
+        .load::<Uuid>(conn)
+        .await?
+        .into_iter()
+        .collect::<HashSet<Uuid>>();
+
+    Ok(requests
+        .into_iter()
+        .map(|request| ProductRequestRes {
+            votes: votes.get(&request.uuid).copied().unwrap_or(0),
+            voted: voted.contains(&request.uuid),
+            request,
+        })
+        .collect())
+}
+
+/// [`with_votes`] without the staff only fields, for the resident facing routes.
+async fn for_resident(
+    conn: &mut AsyncPgConnection,
+    requests: Vec<ProductRequest>,
+    user: Uuid,
+) -> Result<Vec<ResidentProductRequestRes>, AppError> {
+    Ok(with_votes(conn, requests, user)
+        .await?
+        .into_iter()
+        .map(|res| ResidentProductRequestRes::new(res, user))
+        .collect())
+}
+
+fn status_error(from: ProductRequestStatus, to: ProductRequestStatus) -> AppError {
+    let errors = vec![format!(
+        "Request is {:?} and cannot be changed to {:?}",
+        from, to
+    )];
+    AppError::bad_request::<ClientErrorMessages>(DataValidationError { errors }.into())
+}
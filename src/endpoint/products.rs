@@ -1,20 +1,75 @@
-use crate::models::products::Product;
-use crate::req_res::products::SearchParams;
-use crate::req_res::AppError;
+use crate::backend::catalogue::{category_subtree, load_tags};
+use crate::models::products::{Category, Product, ProductRecord, Tag};
+use crate::req_res::products::{ProductQuery, ProductSort, SearchParams, SortOrder};
+use crate::req_res::{AppError, Paginated};
+use crate::schema::private;
 use crate::AppState;
-use axum::extract::{Query, State};
+use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::routing::get;
 use axum::{Json, Router};
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
-use diesel_full_text_search::{websearch_to_tsquery, TsVectorExtensions};
+use diesel_full_text_search::{ts_rank, websearch_to_tsquery, TsVectorExtensions};
 use std::sync::Arc;
 use uuid::Uuid as UuidType;
 
 pub fn get_routes() -> Router<Arc<AppState>> {
-    Router::new().nest("/products/", Router::new().route("/", get(get_products)))
+    Router::new().nest(
+        "/products/",
+        Router::new()
+            .route("/", get(get_products))
+            .route("/categories", get(get_categories))
+            .route("/tags", get(get_tags))
+            .route("/{uid}", get(get_product)),
+    )
+}
+
+/// Archived products are still returned so order history can show what was bought.
+async fn get_product(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<UuidType>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    use crate::schema::private::products::dsl::*;
+
+    let record = products
+        .filter(uuid.eq(uid))
+        .select(ProductRecord::as_select())
+        .first::<ProductRecord>(&mut con)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)?;
+    let product_tags = load_tags(&mut con, &[uid]).await?.remove(&uid);
+    let product = Product::new(record, product_tags.unwrap_or_default());
+
+    Ok((StatusCode::OK, Json(product)))
+}
+
+async fn get_categories(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let categories = private::categories::table
+        .select(Category::as_select())
+        .order(private::categories::name.asc())
+        .load::<Category>(&mut con)
+        .await?;
+
+    Ok((StatusCode::OK, Json(categories)))
+}
+
+async fn get_tags(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let tags = private::tags::table
+        .select(Tag::as_select())
+        .order(private::tags::name.asc())
+        .load::<Tag>(&mut con)
+        .await?;
+
+    Ok((StatusCode::OK, Json(tags)))
 }
 
 async fn get_products(
@@ -23,28 +78,92 @@ async fn get_products(
 ) -> Result<impl IntoResponse, AppError> {
     let pool = &state.postgres_pool;
     let mut con = pool.get().await?;
+    let req: ProductQuery = params.try_into()?;
+    let categories = match req.category {
+        Some(root) => Some(category_subtree(&mut con, root).await?),
+        None => None,
+    };
     use crate::schema::private::products::dsl::*;
-    let mut query = products
-        .select((uuid, title, image_path, description, stock, cost))
-        .into_boxed();
 
-    if let Some(search_term) = &params.q {
-        query = query.filter(search_vector.matches(websearch_to_tsquery(search_term)));
-    }
+    let filtered = || {
+        let mut query = products.filter(archived_at.is_null()).into_boxed();
+        if let Some(search_term) = &req.q {
+            query = query.filter(search_vector.matches(websearch_to_tsquery(search_term)));
+        }
+        if let Some(min_cost) = req.min_cost {
+            query = query.filter(cost.ge(min_cost));
+        }
+        if let Some(max_cost) = req.max_cost {
+            query = query.filter(cost.le(max_cost));
+        }
+        if req.in_stock {
+            query = query.filter(stock.gt(0));
+        }
+        if let Some(ids) = &categories {
+            query = query.filter(category_id.eq_any(ids.clone()));
+        }
+        if let Some(tag) = &req.tag {
+            query = query.filter(
+                uuid.eq_any(
+                    private::product_tags::table
+                        .inner_join(private::tags::table)
+                        .filter(private::tags::name.eq(tag.clone()))
+                        .select(private::product_tags::product_uuid),
+                ),
+            );
+        }
+        query
+    };
+
+    let total: i64 = filtered().count().get_result(&mut con).await?;
+
+    let mut query = filtered().select(ProductRecord::as_select());
+    query = match (req.sort, req.order) {
+        (ProductSort::Title, SortOrder::Asc) => query.order(title.asc()),
+        (ProductSort::Title, SortOrder::Desc) => query.order(title.desc()),
+        (ProductSort::Cost, SortOrder::Asc) => query.order(cost.asc()),
+        (ProductSort::Cost, SortOrder::Desc) => query.order(cost.desc()),
+        (ProductSort::Stock, SortOrder::Asc) => query.order(stock.asc()),
+        (ProductSort::Stock, SortOrder::Desc) => query.order(stock.desc()),
+        (ProductSort::Relevance, order) => {
+            // Validation guarantees a search term when sorting by relevance
+            let rank = ts_rank(
+                search_vector,
+                websearch_to_tsquery(req.q.clone().unwrap_or_default()),
+            );
+            match order {
+                SortOrder::Asc => query.order(rank.asc()),
+                SortOrder::Desc => query.order(rank.desc()),
+            }
+        }
+    };
 
-    let product_vec = query
-        .get_results::<(UuidType, String, String, String, i32, i32)>(&mut con)
-        .await?
+    let records = query
+        .then_order_by(uuid.asc())
+        .limit(req.per_page)
+        .offset((req.page - 1) * req.per_page)
+        .get_results::<ProductRecord>(&mut con)
+        .await?;
+    let uids = records
+        .iter()
+        .map(|record| record.uuid)
+        .collect::<Vec<UuidType>>();
+    let mut product_tags = load_tags(&mut con, &uids).await?;
+
+    let items = records
         .into_iter()
-        .map(|(uid, p_title, image_p, p_desc, p_stock, p_cost)| Product {
-            uuid: uid,
-            title: p_title,
-            image_path: image_p,
-            description: p_desc,
-            stock: p_stock,
-            cost: p_cost,
+        .map(|record| {
+            let record_tags = product_tags.remove(&record.uuid).unwrap_or_default();
+            Product::new(record, record_tags)
         })
         .collect::<Vec<Product>>();
 
-    Ok((StatusCode::OK, Json(product_vec)))
+    let res = Paginated {
+        items,
+        page: req.page,
+        per_page: req.per_page,
+        total,
+    };
+
+    Ok((StatusCode::OK, Json(res)))
 }
@@ -1,14 +1,24 @@
+use crate::models::products::ImageSize;
+use crate::req_res::products::UploadQuery;
 use crate::req_res::AppError;
 use axum::body::Body;
 use axum::{
-    extract::Path,
+    extract::{Path, Query},
     http::{header, HeaderMap, StatusCode},
     response::IntoResponse,
 };
-use std::path::PathBuf;
+use std::path::{Path as StdPath, PathBuf};
 use tokio::fs::File;
 
-pub async fn serve_upload(Path(file_path): Path<String>) -> Result<impl IntoResponse, AppError> {
+/// Uploads under this folder of `uploads` are private, see `tasks::get_submission_photo`
+const PRIVATE_UPLOAD_DIR: &str = "tasks";
+
+/// Serves a stored upload. Product images accept `size` to get a smaller variant, falling back
+/// to the full size for images stored before variants existed.
+pub async fn serve_upload(
+    Path(file_path): Path<String>,
+    Query(query): Query<UploadQuery>,
+) -> Result<impl IntoResponse, AppError> {
     if file_path.contains("..") || file_path.starts_with('/') || file_path.contains('\\') {
         return Err(AppError::forbidden());
     }
@@ -17,20 +27,35 @@ pub async fn serve_upload(Path(file_path): Path<String>) -> Result<impl IntoResp
         .canonicalize()
         .map_err(|_| AppError::internal_error("Unable to access uploads directory".to_string()))?;
 
-    let path = uploads_dir.join(file_path);
+    let path = uploads_dir.join(&file_path);
+    let variant = match (query.size, file_path.strip_suffix(".webp")) {
+        (Some(size), Some(stem)) if size != ImageSize::Full => uploads_dir
+            .join(format!("{}{}.webp", stem, size.suffix()))
+            .canonicalize()
+            .ok(),
+        _ => None,
+    };
 
-    let canonical_path = path.canonicalize().map_err(|_| AppError::not_found())?;
+    let canonical_path = match variant {
+        Some(variant) => variant,
+        None => path.canonicalize().map_err(|_| AppError::not_found())?,
+    };
 
-    if !canonical_path.starts_with(&uploads_dir) {
+    if !canonical_path.starts_with(&uploads_dir)
+        || canonical_path.starts_with(uploads_dir.join(PRIVATE_UPLOAD_DIR))
+    {
         return Err(AppError::forbidden());
     }
 
-    let file = File::open(&canonical_path)
-        .await
-        .map_err(|_| AppError::not_found())?;
+    serve_file(&canonical_path).await
+}
+
+/// Streams a file from disk, for handlers that have already checked access to it.
+pub async fn serve_file(path: &StdPath) -> Result<impl IntoResponse, AppError> {
+    let file = File::open(path).await.map_err(|_| AppError::not_found())?;
 
     let mut headers = HeaderMap::new();
-    if canonical_path.extension().and_then(|e| e.to_str()) == Some("webp") {
+    if path.extension().and_then(|e| e.to_str()) == Some("webp") {
         headers.insert(header::CONTENT_TYPE, "image/webp".parse().unwrap());
     }
 
@@ -0,0 +1,633 @@
+use crate::backend::audit::{record_audit, snapshot};
+use crate::backend::wallet::credit_wallet;
+use crate::endpoint::public::serve_file;
+use crate::helper::save_task_photo;
+use crate::models::audit::AuditAction;
+use crate::models::tasks::{SubmissionStatus, Task, TaskSubmission};
+use crate::models::wallet::Wallet;
+use crate::paseto::AuthTokenClaims;
+use crate::req_res::tasks::{
+    NewTask, NewTaskReq, RejectSubmission, RejectSubmissionReq, SubmissionQueryParams,
+    SubmissionRes, TaskRes,
+};
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use crate::schema::private;
+use crate::websocket::emit_balance_changed;
+use crate::AppState;
+use axum::extract::{Multipart, Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use bytes::Bytes;
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use log::error;
+use pasetors::claims::Claims;
+use std::collections::HashMap;
+use std::path::Path as StdPath;
+use std::sync::Arc;
+use uuid::Uuid;
+
+const MAX_NOTE_LENGTH: usize = 1000;
+
+pub fn get_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .nest(
+            "/tasks/",
+            Router::new()
+                .route("/", get(get_open_tasks))
+                .route("/mine", get(get_my_submissions))
+                .route("/submissions/{uid}/photo", get(get_my_submission_photo))
+                .route("/{uid}/claim", post(claim_task))
+                .route("/{uid}/submit", post(submit_task)),
+        )
+        .nest(
+            "/inventory/tasks/",
+            Router::new()
+                .route("/", get(get_tasks).post(create_task))
+                .route("/{uid}/close", post(close_task))
+                .route("/{uid}/submissions", get(get_task_submissions))
+                .route("/submissions/{uid}/photo", get(get_submission_photo))
+                .route("/submissions/{uid}/approve", post(approve_submission))
+                .route("/submissions/{uid}/reject", post(reject_submission)),
+        )
+}
+
+/// Tasks a resident can still claim, along with any claim they already hold.
+async fn get_open_tasks(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let tasks = private::tasks::table
+        .filter(private::tasks::closed_at.is_null())
+        .filter(private::tasks::deadline.gt(Utc::now().naive_utc()))
+        .select(Task::as_select())
+        .order(private::tasks::deadline.asc())
+        .load::<Task>(&mut con)
+        .await?;
+    let res = with_counts(&mut con, tasks, Some(claims.user_uid)).await?;
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+async fn get_my_submissions(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let res = private::task_submissions::table
+        .inner_join(private::tasks::table)
+        .filter(private::task_submissions::user_uuid.eq(claims.user_uid))
+        .select((TaskSubmission::as_select(), Task::as_select()))
+        .order(private::task_submissions::claimed_at.desc())
+        .load::<(TaskSubmission, Task)>(&mut con)
+        .await?
+        .into_iter()
+        .map(SubmissionRes::from)
+        .collect::<Vec<SubmissionRes>>();
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+/// The proof photo of one of the caller's own submissions.
+async fn get_my_submission_photo(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let photo_path = private::task_submissions::table
+        .find(uid)
+        .filter(private::task_submissions::user_uuid.eq(claims.user_uid))
+        .select(private::task_submissions::photo_path)
+        .first::<Option<String>>(&mut con)
+        .await
+        .optional()?
+        .flatten()
+        .ok_or_else(AppError::not_found)?;
+
+    serve_file(StdPath::new(&photo_path)).await
+}
+
+async fn claim_task(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let submission = con
+        .transaction(|conn| {
+            async move {
+                // Locking the task serialises claims, so capacity cannot be exceeded
+                let task = lock_open_task(conn, uid).await?;
+
+                let existing = private::task_submissions::table
+                    .filter(private::task_submissions::task_uuid.eq(uid))
+                    .filter(private::task_submissions::user_uuid.eq(claims.user_uid))
+                    .count()
+                    .get_result::<i64>(conn)
+                    .await?;
+                if existing > 0 {
+                    return Err(task_error("You have already claimed this task"));
+                }
+
+                let taken = private::task_submissions::table
+                    .filter(private::task_submissions::task_uuid.eq(uid))
+                    .filter(private::task_submissions::status.ne(SubmissionStatus::Rejected))
+                    .count()
+                    .get_result::<i64>(conn)
+                    .await?;
+                if taken >= i64::from(task.capacity) {
+                    return Err(task_error("Task is full"));
+                }
+
+                let submission = diesel::insert_into(private::task_submissions::table)
+                    .values((
+                        private::task_submissions::task_uuid.eq(uid),
+                        private::task_submissions::user_uuid.eq(claims.user_uid),
+                    ))
+                    .returning(TaskSubmission::as_returning())
+                    .get_result::<TaskSubmission>(conn)
+                    .await?;
+                Ok::<SubmissionRes, AppError>((submission, task).into())
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(submission)))
+}
+
+/// Marks a claimed task as done, with an optional `note` and `photo` as proof.
+async fn submit_task(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let mut note: Option<String> = None;
+    let mut photo: Option<Bytes> = None;
+    while let Some(field) = multipart.next_field().await.map_err(AppError::from)? {
+        match field.name().unwrap_or_default() {
+            "note" => note = Some(field.text().await.map_err(AppError::from)?),
+            "photo" => photo = Some(field.bytes().await.map_err(AppError::from)?),
+            _ => continue,
+        }
+    }
+    let note = note
+        .map(|note| note.trim().to_string())
+        .filter(|note| !note.is_empty());
+    if note
+        .as_ref()
+        .is_some_and(|note| note.chars().count() > MAX_NOTE_LENGTH)
+    {
+        return Err(task_error(&format!(
+            "Note cannot exceed {} characters",
+            MAX_NOTE_LENGTH
+        )));
+    }
+
+    let (submission, task) = private::task_submissions::table
+        .inner_join(private::tasks::table)
+        .filter(private::task_submissions::task_uuid.eq(uid))
+        .filter(private::task_submissions::user_uuid.eq(claims.user_uid))
+        .select((TaskSubmission::as_select(), Task::as_select()))
+        .first::<(TaskSubmission, Task)>(&mut con)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)?;
+    if submission.status != SubmissionStatus::Claimed {
+        return Err(task_error("Task has already been submitted"));
+    }
+    if task.closed_at.is_some() || task.deadline <= Utc::now().naive_utc() {
+        return Err(task_error("Task is no longer open"));
+    }
+
+    let photo_path = match photo {
+        Some(photo) => Some(save_task_photo(&photo, submission.uuid).await?),
+        None => None,
+    };
+
+    // Only move out of `claimed`, so a double submit cannot overwrite the first one
+    let submission = diesel::update(private::task_submissions::table.find(submission.uuid))
+        .filter(private::task_submissions::status.eq(SubmissionStatus::Claimed))
+        .set((
+            private::task_submissions::status.eq(SubmissionStatus::Submitted),
+            private::task_submissions::note.eq(note),
+            private::task_submissions::photo_path.eq(photo_path),
+            private::task_submissions::submitted_at.eq(diesel::dsl::now),
+        ))
+        .returning(TaskSubmission::as_returning())
+        .get_result::<TaskSubmission>(&mut con)
+        .await
+        .optional()?
+        .ok_or_else(|| task_error("Task has already been submitted"))?;
+
+    let res: SubmissionRes = (submission, task).into();
+    Ok((StatusCode::OK, Json(res)))
+}
+
+async fn get_tasks(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+
+    let tasks = private::tasks::table
+        .select(Task::as_select())
+        .order(private::tasks::created_at.desc())
+        .load::<Task>(&mut con)
+        .await?;
+    let res = with_counts(&mut con, tasks, None).await?;
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+async fn create_task(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+    Json(payload): Json<NewTaskReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+    let req: NewTask = payload.try_into()?;
+
+    let task = con
+        .transaction(|conn| {
+            async move {
+                let task = diesel::insert_into(private::tasks::table)
+                    .values((&req, private::tasks::created_by.eq(claims.user_uid)))
+                    .returning(Task::as_returning())
+                    .get_result::<Task>(conn)
+                    .await?;
+
+                record_audit(
+                    conn,
+                    claims.user_uid,
+                    AuditAction::TaskCreated,
+                    &task.uuid.to_string(),
+                    None,
+                    snapshot(&task),
+                )
+                .await?;
+                Ok::<Task, AppError>(task)
+            }
+            .scope_boxed()
+        })
+        .await?;
+    let res = with_counts(&mut con, vec![task], None).await?.remove(0);
+
+    Ok((StatusCode::CREATED, Json(res)))
+}
+
+/// Stops new claims before the deadline. Submissions already made can still be reviewed.
+async fn close_task(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let task = con
+        .transaction(|conn| {
+            async move {
+                let before = lock_task(conn, uid).await?;
+                if before.closed_at.is_some() {
+                    return Err(task_error("Task is already closed"));
+                }
+                let after = diesel::update(private::tasks::table.find(uid))
+                    .set(private::tasks::closed_at.eq(diesel::dsl::now))
+                    .returning(Task::as_returning())
+                    .get_result::<Task>(conn)
+                    .await?;
+
+                record_audit(
+                    conn,
+                    claims.user_uid,
+                    AuditAction::TaskClosed,
+                    &uid.to_string(),
+                    snapshot(&before),
+                    snapshot(&after),
+                )
+                .await?;
+                Ok::<Task, AppError>(after)
+            }
+            .scope_boxed()
+        })
+        .await?;
+    let res = with_counts(&mut con, vec![task], None).await?.remove(0);
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+async fn get_task_submissions(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Query(params): Query<SubmissionQueryParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+
+    let mut query = private::task_submissions::table
+        .inner_join(private::tasks::table)
+        .filter(private::task_submissions::task_uuid.eq(uid))
+        .select((TaskSubmission::as_select(), Task::as_select()))
+        .order(private::task_submissions::claimed_at.asc())
+        .into_boxed();
+
+    if let Some(status) = params.status {
+        query = query.filter(private::task_submissions::status.eq(status));
+    }
+
+    let res = query
+        .load::<(TaskSubmission, Task)>(&mut con)
+        .await?
+        .into_iter()
+        .map(SubmissionRes::from)
+        .collect::<Vec<SubmissionRes>>();
+
+    Ok((StatusCode::OK, Json(res)))
+}
+
+/// Task photos are kept out of the public `/uploads` route, staff fetch them here.
+async fn get_submission_photo(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+
+    let photo_path = private::task_submissions::table
+        .find(uid)
+        .select(private::task_submissions::photo_path)
+        .first::<Option<String>>(&mut con)
+        .await
+        .optional()?
+        .flatten()
+        .ok_or_else(AppError::not_found)?;
+
+    serve_file(StdPath::new(&photo_path)).await
+}
+
+/// Approves a submitted task and credits the reward to the resident's wallet.
+async fn approve_submission(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let (submission, wallet) = con
+        .transaction(|conn| {
+            async move {
+                let (submission, task) = review_submission(
+                    conn,
+                    claims.user_uid,
+                    uid,
+                    &[SubmissionStatus::Submitted],
+                    SubmissionStatus::Approved,
+                    None,
+                )
+                .await?;
+
+                let wallet = credit_wallet(
+                    conn,
+                    submission.user_uuid,
+                    task.reward,
+                    &format!("Task {}", task.uuid),
+                    None,
+                )
+                .await?;
+
+                Ok::<(SubmissionRes, Wallet), AppError>(((submission, task).into(), wallet))
+            }
+            .scope_boxed()
+        })
+        .await?;
+    emit_balance_changed(&state.io, wallet.user_uuid, wallet.balance);
+
+    Ok((StatusCode::OK, Json(submission)))
+}
+
+/// Rejects a submission, or a claim that was never submitted, freeing its slot.
+async fn reject_submission(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+    Json(payload): Json<RejectSubmissionReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+    let req: RejectSubmission = payload.try_into()?;
+
+    let submission = con
+        .transaction(|conn| {
+            async move {
+                let (submission, task) = review_submission(
+                    conn,
+                    claims.user_uid,
+                    uid,
+                    &[SubmissionStatus::Claimed, SubmissionStatus::Submitted],
+                    SubmissionStatus::Rejected,
+                    Some(req.reason),
+                )
+                .await?;
+                Ok::<SubmissionRes, AppError>((submission, task).into())
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok((StatusCode::OK, Json(submission)))
+}
+
+/// Moves a submission to `to` if it is currently in one of the `from` states and
+/// records the review against the acting staff member.
+async fn review_submission(
+    conn: &mut AsyncPgConnection,
+    actor: Uuid,
+    uid: Uuid,
+    from: &[SubmissionStatus],
+    to: SubmissionStatus,
+    reason: Option<String>,
+) -> Result<(TaskSubmission, Task), AppError> {
+    let submission = private::task_submissions::table
+        .find(uid)
+        .select(TaskSubmission::as_select())
+        .for_update()
+        .first::<TaskSubmission>(conn)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)?;
+
+    if !from.contains(&submission.status) {
+        let errors = vec![format!(
+            "Submission is {:?} and cannot be changed to {:?}",
+            submission.status, to
+        )];
+        return Err(AppError::bad_request::<ClientErrorMessages>(
+            DataValidationError { errors }.into(),
+        ));
+    }
+
+    let updated = diesel::update(private::task_submissions::table.find(uid))
+        .set((
+            private::task_submissions::status.eq(to),
+            private::task_submissions::review_reason.eq(reason),
+            private::task_submissions::reviewed_by.eq(actor),
+            private::task_submissions::reviewed_at.eq(diesel::dsl::now),
+        ))
+        .returning(TaskSubmission::as_returning())
+        .get_result::<TaskSubmission>(conn)
+        .await?;
+
+    let action = match to {
+        SubmissionStatus::Approved => AuditAction::TaskSubmissionApproved,
+        _ => AuditAction::TaskSubmissionRejected,
+    };
+    record_audit(
+        conn,
+        actor,
+        action,
+        &uid.to_string(),
+        snapshot(&submission),
+        snapshot(&updated),
+    )
+    .await?;
+
+    let task = private::tasks::table
+        .find(updated.task_uuid)
+        .select(Task::as_select())
+        .first::<Task>(conn)
+        .await?;
+    Ok((updated, task))
+}
+
+async fn lock_task(conn: &mut AsyncPgConnection, uid: Uuid) -> Result<Task, AppError> {
+    private::tasks::table
+        .find(uid)
+        .select(Task::as_select())
+        .for_update()
+        .first::<Task>(conn)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)
+}
+
+async fn lock_open_task(conn: &mut AsyncPgConnection, uid: Uuid) -> Result<Task, AppError> {
+    let task = lock_task(conn, uid).await?;
+    if task.closed_at.is_some() || task.deadline <= Utc::now().naive_utc() {
+        return Err(task_error("Task is no longer open"));
+    }
+    Ok(task)
+}
+
+/// Adds how many slots are taken and, for residents, their own claim status.
+async fn with_counts(
+    conn: &mut AsyncPgConnection,
+    tasks: Vec<Task>,
+    user: Option<Uuid>,
+) -> Result<Vec<TaskRes>, AppError> {
+    let task_uids = tasks.iter().map(|t| t.uuid).collect::<Vec<Uuid>>();
+    let taken = private::task_submissions::table
+        .filter(private::task_submissions::task_uuid.eq_any(&task_uids))
+        .filter(private::task_submissions::status.ne(SubmissionStatus::Rejected))
+        .group_by(private::task_submissions::task_uuid)
+        .select((
+            private::task_submissions::task_uuid,
+            diesel::dsl::count_star(),
+        ))
+        .load::<(Uuid, i64)>(conn)
+        .await?
+        .into_iter()
+        .collect::<HashMap<Uuid, i64>>();
+
+    let mut mine = HashMap::new();
+    if let Some(user) = user {
+        mine = private::task_submissions::table
+            .filter(private::task_submissions::task_uuid.eq_any(&task_uids))
+            .filter(private::task_submissions::user_uuid.eq(user))
+            .select((
+                private::task_submissions::task_uuid,
+                private::task_submissions::status,
+            ))
+            .load::<(Uuid, SubmissionStatus)>(conn)
+            .await?
+            .into_iter()
+            .collect::<HashMap<Uuid, SubmissionStatus>>();
+    }
+
+    Ok(tasks
+        .into_iter()
+        .map(|task| TaskRes {
+            taken: taken.get(&task.uuid).copied().unwrap_or(0),
+            my_status: mine.get(&task.uuid).copied(),
+            task,
+        })
+        .collect())
+}
+
+fn task_error(message: &str) -> AppError {
+    let errors = vec![message.to_string()];
+    AppError::bad_request::<ClientErrorMessages>(DataValidationError { errors }.into())
+}
@@ -1,16 +1,28 @@
+use crate::backend::audit::{record_audit, snapshot};
+use crate::backend::csv_import::read_rows;
+use crate::backend::sessions::revoke_user_sessions;
+use crate::backend::wallet::{credit_wallet, debit_wallet};
 use crate::helper::hash_password;
+use crate::models::audit::AuditAction;
 use crate::models::user::{AccountType, User};
 use crate::models::wallet::Wallet;
+use crate::notifications::queue::notify_user;
+use crate::notifications::templates::Template;
 use crate::paseto::AuthTokenClaims;
 use crate::req_res::auth::{NewUser, RedactedUser};
 use crate::req_res::me::UpdateUser;
-use crate::req_res::users::{AdminNewUserReq, AdminUpdateUserReq, DetailedUser, DetailedUserFull};
-use crate::req_res::AppError;
+use crate::req_res::users::{
+    AdminNewUserReq, AdminUpdateUserReq, CredentialDelivery, DetailedUser, DetailedUserFull,
+    GeneratedCredential, ImportUsersQuery, NewUserRes, ResidentCsvRow, UserImportReport,
+    WalletAdjustment, WalletAdjustmentReq,
+};
+use crate::req_res::{AppError, ImportRowError};
 use crate::schema::private;
 use crate::schema::private::users::uuid as SqlUuid;
 use crate::utils::generate_random_string;
+use crate::websocket::{disconnect_user, emit_balance_changed};
 use crate::AppState;
-use axum::extract::{Path, State};
+use axum::extract::{Multipart, Path, Query, State};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::routing::{get, post};
@@ -18,9 +30,11 @@ use axum::{Extension, Json, Router};
 use diesel::associations::HasTable;
 use diesel::prelude::*;
 use diesel_async::scoped_futures::ScopedFutureExt;
-use diesel_async::{AsyncConnection, RunQueryDsl};
-use log::error;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use log::{error, info};
 use pasetors::claims::Claims;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -29,13 +43,16 @@ pub fn get_routes() -> Router<Arc<AppState>> {
         "/users/",
         Router::new()
             .route("/", get(get_users).post(create_user))
+            .route("/import", post(import_users))
             .route(
                 "/{id}",
                 get(get_user).patch(update_user).delete(delete_user),
             )
             .route("/{id}/suspend", post(suspend_user))
             .route("/{id}/activate", post(unsuspend_user))
-            .route("/{id}/reset-password", post(reset_password)),
+            .route("/{id}/reset-password", post(reset_password))
+            .route("/{id}/wallet/credit", post(credit_user_wallet))
+            .route("/{id}/wallet/debit", post(debit_user_wallet)),
     )
 }
 
@@ -81,10 +98,16 @@ async fn get_user(
 }
 async fn create_user(
     State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
     Json(payload): Json<AdminNewUserReq>,
 ) -> Result<impl IntoResponse, AppError> {
     let pool = &state.postgres_pool;
     let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
     let mut n_user: NewUser = payload.try_into()?;
     let random_password = generate_random_string();
     n_user.password = hash_password(&random_password)?;
@@ -104,36 +127,255 @@ async fn create_user(
                     .get_result::<Wallet>(conn)
                     .await?;
 
-                Ok::<(User, Wallet), diesel::result::Error>((user, wallet))
+                record_audit(
+                    conn,
+                    claims.user_uid,
+                    AuditAction::UserCreated,
+                    &user.uuid.to_string(),
+                    None,
+                    snapshot(&user),
+                )
+                .await?;
+
+                Ok::<(User, Wallet), AppError>((user, wallet))
             }
             .scope_boxed()
         })
-        .await
-        .map_err(AppError::from)?;
+        .await?;
+
+    let template = Template::Welcome {
+        name: &created_user.name,
+        resident_id: &created_user.resident_id,
+        password: &random_password,
+    };
+    // The user already exists, so a queueing failure must not turn into an error response
+    let delivered = match notify_user(&state.redis_client, &created_user, &template).await {
+        Ok(()) => true,
+        Err(e) => {
+            error!(
+                "Unable to queue welcome message for {}: {:?}",
+                created_user.uuid, e
+            );
+            false
+        }
+    };
+
+    let res = NewUserRes {
+        user: (created_user, Some(user_wallet)).into(),
+        delivery: CredentialDelivery::new(delivered, random_password),
+    };
+    Ok((StatusCode::CREATED, Json(res)))
+}
+
+/// Onboards residents from a CSV. Every row is validated before anything is written and the
+/// whole batch is created in one transaction, `dry_run` only validates.
+async fn import_users(
+    State(state): State<Arc<AppState>>,
+    Extension(c): Extension<Option<Claims>>,
+    Query(query): Query<ImportUsersQuery>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    let mut csv_data = None;
+    while let Some(field) = multipart.next_field().await.map_err(AppError::from)? {
+        if field.name() == Some("file") {
+            csv_data = Some(field.bytes().await.map_err(AppError::from)?);
+        }
+    }
+    let csv_data = csv_data.ok_or_else(|| AppError::bad_request(None))?;
+    let rows = read_rows::<ResidentCsvRow>(&csv_data);
+
+    let resident_ids = rows
+        .iter()
+        .filter_map(|(_, row)| row.as_ref().ok())
+        .map(|row| row.resident_id.clone())
+        .collect::<Vec<String>>();
+    let existing = private::users::table
+        .filter(private::users::resident_id.eq_any(&resident_ids))
+        .select(private::users::resident_id)
+        .load::<String>(&mut con)
+        .await?
+        .into_iter()
+        .collect::<HashSet<String>>();
+
+    let mut report = UserImportReport {
+        dry_run: query.dry_run.unwrap_or(false),
+        delivered: query.deliver.unwrap_or(false),
+        ..Default::default()
+    };
+    let mut users = vec![];
+    let mut seen = HashSet::new();
+    for (line, row) in rows {
+        let mut errors = vec![];
+        let req = row.and_then(AdminNewUserReq::try_from);
+        let req = match req {
+            Ok(req) => req,
+            Err(e) => {
+                report.errors.push(ImportRowError {
+                    line,
+                    errors: vec![e],
+                });
+                continue;
+            }
+        };
+        if existing.contains(&req.resident_id) {
+            errors.push(format!("Resident {} already exists", req.resident_id));
+        }
+        if !seen.insert(req.resident_id.clone()) {
+            errors.push(format!(
+                "Resident {} appears more than once",
+                req.resident_id
+            ));
+        }
+        match req.validate() {
+            Ok(user) if errors.is_empty() => users.push(user),
+            Ok(_) => report.errors.push(ImportRowError { line, errors }),
+            Err(invalid) => {
+                errors.extend(invalid);
+                report.errors.push(ImportRowError { line, errors });
+            }
+        }
+    }
+
+    if !report.errors.is_empty() {
+        return Ok((StatusCode::BAD_REQUEST, Json(report)));
+    }
+    report.created = users.len();
+    if report.dry_run {
+        return Ok((StatusCode::OK, Json(report)));
+    }
+
+    // Hashing is deliberately slow, keep a large batch off the async workers
+    let (users, mut passwords) = tokio::task::spawn_blocking(move || {
+        let mut passwords = HashMap::new();
+        for user in &mut users {
+            let password = generate_random_string();
+            user.password = hash_password(&password)?;
+            passwords.insert(user.resident_id.clone(), password);
+        }
+        Ok::<_, AppError>((users, passwords))
+    })
+    .await
+    .map_err(|e| {
+        error!("Password hashing task failed: {}", e);
+        AppError::internal_error("Password hashing failed".to_string())
+    })??;
+
+    let created = con
+        .transaction(|conn| {
+            async move {
+                let created = diesel::insert_into(private::users::table)
+                    .values(&users)
+                    .get_results::<User>(conn)
+                    .await?;
+                let wallets = created
+                    .iter()
+                    .map(|user| {
+                        (
+                            private::wallets::user_uuid.eq(user.uuid),
+                            private::wallets::balance.eq(0),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                diesel::insert_into(private::wallets::table)
+                    .values(&wallets)
+                    .execute(conn)
+                    .await?;
+
+                for user in &created {
+                    record_audit(
+                        conn,
+                        claims.user_uid,
+                        AuditAction::UserCreated,
+                        &user.uuid.to_string(),
+                        None,
+                        snapshot(user),
+                    )
+                    .await?;
+                }
+                Ok::<Vec<User>, AppError>(created)
+            }
+            .scope_boxed()
+        })
+        .await?;
+    info!("Imported {} residents", created.len());
 
-    let detailed: DetailedUser = (created_user, Some(user_wallet)).into();
-    //TODO: Send generated password via mail or text
-    println!("created password: {}", random_password);
-    Ok((StatusCode::CREATED, Json(detailed)))
+    for user in &created {
+        let Some(password) = passwords.remove(&user.resident_id) else {
+            continue;
+        };
+        if report.delivered {
+            let template = Template::Welcome {
+                name: &user.name,
+                resident_id: &user.resident_id,
+                password: &password,
+            };
+            // The residents already exist, so a queueing failure hands the password
+            // back to staff instead of failing the whole import
+            match notify_user(&state.redis_client, user, &template).await {
+                Ok(()) => continue,
+                Err(e) => {
+                    error!("Unable to queue welcome message for {}: {:?}", user.uuid, e);
+                    report.undelivered += 1;
+                }
+            }
+        }
+        report.credentials.push(GeneratedCredential {
+            uuid: user.uuid,
+            resident_id: user.resident_id.clone(),
+            name: user.name.clone(),
+            password,
+        });
+    }
+
+    Ok((StatusCode::CREATED, Json(report)))
 }
 
 async fn update_user(
     State(state): State<Arc<AppState>>,
     Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
     Json(update_user): Json<AdminUpdateUserReq>,
 ) -> Result<impl IntoResponse, AppError> {
     let update_user: UpdateUser = update_user.try_into()?;
 
     let pool = &state.postgres_pool;
     let mut con = pool.get().await?;
-    diesel::update(private::users::table)
-        .filter(SqlUuid.eq(uid))
-        .set(&update_user)
-        .get_result::<User>(&mut con)
-        .await
-        .optional()
-        .map_err(AppError::from)?
-        .ok_or_else(|| AppError::not_found())?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+
+    con.transaction(|conn| {
+        async move {
+            let before = lock_user(conn, uid).await?;
+            let after = diesel::update(private::users::table)
+                .filter(SqlUuid.eq(uid))
+                .set(&update_user)
+                .get_result::<User>(conn)
+                .await?;
+
+            record_audit(
+                conn,
+                claims.user_uid,
+                AuditAction::UserUpdated,
+                &uid.to_string(),
+                snapshot(&before),
+                snapshot(&after),
+            )
+            .await
+        }
+        .scope_boxed()
+    })
+    .await?;
     Ok((StatusCode::OK, ()))
 }
 async fn delete_user(
@@ -153,14 +395,28 @@ async fn delete_user(
         return Err(AppError::bad_request(None));
     }
 
-    let deleted_count = diesel::delete(private::users::table.find(uid))
-        .execute(&mut con)
-        .await
-        .map_err(AppError::from)?;
+    con.transaction(|conn| {
+        async move {
+            let before = lock_user(conn, uid).await?;
+            diesel::delete(private::users::table.find(uid))
+                .execute(conn)
+                .await?;
 
-    if deleted_count == 0 {
-        return Err(AppError::not_found());
-    }
+            record_audit(
+                conn,
+                claims.user_uid,
+                AuditAction::UserDeleted,
+                &uid.to_string(),
+                snapshot(&before),
+                None,
+            )
+            .await
+        }
+        .scope_boxed()
+    })
+    .await?;
+    revoke_user_sessions(&state.redis_client, uid).await?;
+    disconnect_user(&state.io, uid);
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -185,22 +441,52 @@ async fn reset_password(
     let random_password = generate_random_string();
     let hashed_password = hash_password(&random_password)?;
 
-    diesel::update(private::users::table)
-        .filter(SqlUuid.eq(uid))
-        .set((
-            private::users::password.eq(hashed_password),
-            private::users::force_pw_change.eq(true),
-        ))
-        .get_result::<User>(&mut con)
-        .await
-        .optional()
-        .map_err(AppError::from)?
-        .ok_or_else(|| AppError::not_found())?;
+    let user = con
+        .transaction(|conn| {
+            async move {
+                let before = lock_user(conn, uid).await?;
+                let after = diesel::update(private::users::table)
+                    .filter(SqlUuid.eq(uid))
+                    .set((
+                        private::users::password.eq(hashed_password),
+                        private::users::force_pw_change.eq(true),
+                    ))
+                    .get_result::<User>(conn)
+                    .await?;
 
-    //TODO: Send new password via email or text
-    println!("New password: {}", random_password);
+                record_audit(
+                    conn,
+                    claims.user_uid,
+                    AuditAction::UserPasswordReset,
+                    &uid.to_string(),
+                    snapshot(&before),
+                    snapshot(&after),
+                )
+                .await?;
+                Ok::<User, AppError>(after)
+            }
+            .scope_boxed()
+        })
+        .await?;
+    revoke_user_sessions(&state.redis_client, uid).await?;
 
-    Ok(StatusCode::OK)
+    let template = Template::PasswordReset {
+        name: &user.name,
+        password: &random_password,
+    };
+    // The password has already changed, so a queueing failure must not turn into an error response
+    let delivered = match notify_user(&state.redis_client, &user, &template).await {
+        Ok(()) => true,
+        Err(e) => {
+            error!("Unable to queue password reset for {}: {:?}", user.uuid, e);
+            false
+        }
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(CredentialDelivery::new(delivered, random_password)),
+    ))
 }
 
 async fn suspend_user(
@@ -220,14 +506,16 @@ async fn suspend_user(
         return Err(AppError::bad_request(None));
     }
 
-    diesel::update(private::users::table)
-        .filter(SqlUuid.eq(uid))
-        .set(private::users::active.eq(false))
-        .get_result::<User>(&mut con)
-        .await
-        .optional()
-        .map_err(AppError::from)?
-        .ok_or_else(|| AppError::not_found())?;
+    set_user_active(
+        &mut con,
+        claims.user_uid,
+        uid,
+        false,
+        AuditAction::UserSuspended,
+    )
+    .await?;
+    revoke_user_sessions(&state.redis_client, uid).await?;
+    disconnect_user(&state.io, uid);
 
     Ok((StatusCode::OK, ()))
 }
@@ -249,14 +537,151 @@ async fn unsuspend_user(
         return Err(AppError::bad_request(None));
     }
 
-    diesel::update(private::users::table)
-        .filter(SqlUuid.eq(uid))
-        .set(private::users::active.eq(true))
-        .get_result::<User>(&mut con)
-        .await
-        .optional()
-        .map_err(AppError::from)?
-        .ok_or_else(|| AppError::not_found())?;
+    set_user_active(
+        &mut con,
+        claims.user_uid,
+        uid,
+        true,
+        AuditAction::UserActivated,
+    )
+    .await?;
 
     Ok((StatusCode::OK, ()))
 }
+
+async fn credit_user_wallet(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+    Json(payload): Json<WalletAdjustmentReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+    let req: WalletAdjustment = payload.try_into()?;
+
+    let (user, wallet) = con
+        .transaction(|conn| {
+            async move {
+                let wallet =
+                    credit_wallet(conn, uid, req.amount, &req.description, req.expires_at).await?;
+                let previous_balance = wallet.balance - req.amount;
+                record_audit(
+                    conn,
+                    claims.user_uid,
+                    AuditAction::WalletCredited,
+                    &uid.to_string(),
+                    Some(json!({ "balance": previous_balance })),
+                    Some(json!({
+                        "balance": wallet.balance,
+                        "description": req.description,
+                        "expires_at": req.expires_at,
+                    })),
+                )
+                .await?;
+                let user = private::users::table
+                    .find(uid)
+                    .select(User::as_select())
+                    .first::<User>(conn)
+                    .await?;
+                Ok::<(User, Wallet), AppError>((user, wallet))
+            }
+            .scope_boxed()
+        })
+        .await?;
+    emit_balance_changed(&state.io, uid, wallet.balance);
+
+    let detailed: DetailedUserFull = (user, Some(wallet)).into();
+    Ok((StatusCode::OK, Json(detailed)))
+}
+
+async fn debit_user_wallet(
+    State(state): State<Arc<AppState>>,
+    Path(uid): Path<Uuid>,
+    Extension(c): Extension<Option<Claims>>,
+    Json(payload): Json<WalletAdjustmentReq>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.postgres_pool;
+    let mut con = pool.get().await?;
+    let claims = c.ok_or_else(AppError::unauthorized)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        AppError::unauthorized()
+    })?;
+    let req: WalletAdjustment = payload.try_into()?;
+
+    let (user, wallet) = con
+        .transaction(|conn| {
+            async move {
+                let wallet = debit_wallet(conn, uid, req.amount, &req.description, None).await?;
+                let previous_balance = wallet.balance + req.amount;
+                record_audit(
+                    conn,
+                    claims.user_uid,
+                    AuditAction::WalletDebited,
+                    &uid.to_string(),
+                    Some(json!({ "balance": previous_balance })),
+                    Some(json!({ "balance": wallet.balance, "description": req.description })),
+                )
+                .await?;
+                let user = private::users::table
+                    .find(uid)
+                    .select(User::as_select())
+                    .first::<User>(conn)
+                    .await?;
+                Ok::<(User, Wallet), AppError>((user, wallet))
+            }
+            .scope_boxed()
+        })
+        .await?;
+    emit_balance_changed(&state.io, uid, wallet.balance);
+
+    let detailed: DetailedUserFull = (user, Some(wallet)).into();
+    Ok((StatusCode::OK, Json(detailed)))
+}
+
+async fn set_user_active(
+    con: &mut AsyncPgConnection,
+    actor: Uuid,
+    uid: Uuid,
+    active: bool,
+    action: AuditAction,
+) -> Result<(), AppError> {
+    con.transaction(|conn| {
+        async move {
+            let before = lock_user(conn, uid).await?;
+            let after = diesel::update(private::users::table)
+                .filter(SqlUuid.eq(uid))
+                .set(private::users::active.eq(active))
+                .get_result::<User>(conn)
+                .await?;
+
+            record_audit(
+                conn,
+                actor,
+                action,
+                &uid.to_string(),
+                snapshot(&before),
+                snapshot(&after),
+            )
+            .await
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+async fn lock_user(conn: &mut AsyncPgConnection, uid: Uuid) -> Result<User, AppError> {
+    private::users::table
+        .find(uid)
+        .select(User::as_select())
+        .for_update()
+        .first::<User>(conn)
+        .await
+        .optional()?
+        .ok_or_else(AppError::not_found)
+}
@@ -1,10 +1,14 @@
-use crate::paseto::get_private_public_keypair;
+use crate::models::products::{ImageSize, ProductImage};
+use crate::paseto::{get_private_public_keypair, TokenPurpose, TOKEN_AUDIENCE, TOKEN_ISSUER};
 use crate::regex;
 use crate::req_res::AppError;
 use argon2::password_hash::rand_core::OsRng;
 use argon2::password_hash::SaltString;
 use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
-use image::ImageFormat;
+use axum::http::HeaderMap;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageReader};
 use log::{debug, error};
 use pasetors::claims::{Claims, ClaimsValidationRules};
 use pasetors::keys::AsymmetricPublicKey;
@@ -13,24 +17,49 @@ use pasetors::version4::V4;
 use pasetors::{public, Public};
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
+use std::io::Cursor;
+use std::net::{IpAddr, SocketAddr};
 use std::path::Path;
+use std::time::Duration;
 use trust_dns_resolver::config::{ResolverConfig, ResolverOpts};
 use trust_dns_resolver::proto::rr::RecordType;
 use trust_dns_resolver::AsyncResolver;
 use uuid::Uuid;
 use webp::Encoder;
 
-pub fn validate_token(token: &str) -> Option<(String, Claims)> {
+const ORPHAN_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+pub fn validate_token(token: &str, purpose: TokenPurpose) -> Option<(String, Claims)> {
     let (_, public_key) = get_private_public_keypair();
     let pk = AsymmetricPublicKey::<V4>::try_from(public_key.as_str()).ok()?;
-    let validation_rules = ClaimsValidationRules::new();
+    let mut validation_rules = ClaimsValidationRules::new();
+    validation_rules.validate_issuer_with(TOKEN_ISSUER);
+    validation_rules.validate_audience_with(TOKEN_AUDIENCE);
     let untrusted_token = UntrustedToken::<Public, V4>::try_from(token).ok()?;
     let trusted = public::verify(&pk, &untrusted_token, &validation_rules, None, None).ok()?;
     let claims = trusted.payload_claims()?;
+    // Never let a refresh token stand in for an access token or vice versa
+    if claims.get_claim("purpose")?.as_str()? != purpose.as_str() {
+        return None;
+    }
     let role = claims.get_claim("role")?.as_str()?;
     Some((role.to_string(), claims.clone()))
 }
 
+pub fn client_ip(headers: &HeaderMap, addr: SocketAddr, trust_proxy: bool) -> IpAddr {
+    if trust_proxy {
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+            .and_then(|ip| ip.trim().parse::<IpAddr>().ok());
+        if let Some(ip) = forwarded {
+            return ip;
+        }
+    }
+    addr.ip()
+}
+
 pub fn hash_password(password: &str) -> Result<String, AppError> {
     // Reference https://github.com/OWASP/CheatSheetSeries/blob/master/cheatsheets/Password_Storage_Cheat_Sheet.md#argon2id
     let parameters = Params::new(19456, 2, 1, None).unwrap();
@@ -131,38 +160,160 @@ pub async fn is_bad_mail(email: &str) -> bool {
     }
 }
 
-pub async fn save_product_image(
+/// Stores a product photo as a WebP per `ImageSize`, returning the path of each variant.
+pub async fn save_product_image(image_data: &[u8]) -> Result<ProductImage, AppError> {
+    let mut image = ProductImage::default();
+    let variants = save_image_variants(
+        "uploads/products",
+        Uuid::new_v4(),
+        image_data,
+        &ImageSize::ALL,
+    )
+    .await?;
+    for (size, path) in variants {
+        match size {
+            ImageSize::Thumbnail => image.thumbnail = path,
+            ImageSize::Card => image.card = path,
+            ImageSize::Full => image.full = path,
+        }
+    }
+    Ok(image)
+}
+
+/// Deletes the files of images that were saved but never ended up on a product.
+pub async fn remove_product_images(images: &[ProductImage]) {
+    for image in images {
+        for path in [&image.full, &image.card, &image.thumbnail] {
+            if let Err(e) = tokio::fs::remove_file(path).await {
+                error!("Failed to remove product image {}: {}", path, e);
+            }
+        }
+    }
+}
+
+/// Stores a task completion photo through the same pipeline as product images, only at full
+/// size as staff review the photo one at a time. Returns the path of the stored file.
+pub async fn save_task_photo(image_data: &[u8], submission: Uuid) -> Result<String, AppError> {
+    save_image_variants("uploads/tasks", submission, image_data, &[ImageSize::Full])
+        .await?
+        .into_iter()
+        .find(|(size, _)| *size == ImageSize::Full)
+        .map(|(_, path)| path)
+        .ok_or_else(|| AppError::internal_error("Fail to save task photo".to_string()))
+}
+
+/// Files are named after `name` rather than anything user supplied, so a stored
+/// path can never leave `upload_dir`.
+async fn save_image_variants(
+    upload_dir: &str,
+    name: Uuid,
     image_data: &[u8],
-    product_title: &str,
-) -> Result<String, AppError> {
-    let upload_dir = Path::new("uploads/products");
+    sizes: &[ImageSize],
+) -> Result<Vec<(ImageSize, String)>, AppError> {
     tokio::fs::create_dir_all(upload_dir).await.map_err(|e| {
         error!("Failed to create directory: {}", e);
-        AppError::internal_error("Fail to save product image".to_string())
+        AppError::internal_error("Fail to save image".to_string())
     })?;
 
-    let img = image::load_from_memory(image_data).map_err(|e| AppError::bad_request(None))?;
+    let image_data = image_data.to_vec();
+    let sizes = sizes.to_vec();
+    let variants = tokio::task::spawn_blocking(move || encode_image_variants(&image_data, &sizes))
+        .await
+        .map_err(|e| {
+            error!("Image encoding task failed: {}", e);
+            AppError::internal_error("Fail to save image".to_string())
+        })??;
 
     let random_suffix: String = thread_rng()
         .sample_iter(&Alphanumeric)
         .take(6)
         .map(char::from)
         .collect();
+    let stem = format!("{}_{}", name, random_suffix);
 
-    let filename = format!("{}_{}.webp", product_title, random_suffix);
-    let path = upload_dir.join(&filename);
+    let mut paths = vec![];
+    for (size, webp_data) in variants {
+        let path = format!("{}/{}{}.webp", upload_dir, stem, size.suffix());
+        tokio::fs::write(&path, webp_data).await.map_err(|e| {
+            error!("Fail to write image file: {}", e);
+            AppError::internal_error("Fail to save image".to_string())
+        })?;
+        paths.push((size, path));
+    }
 
-    let encoder = Encoder::from_image(&img).map_err(|e| {
-        error!("WebP encoding error: {}", e);
-        AppError::internal_error("Fail to save product image".to_string())
-    })?;
+    Ok(paths)
+}
+
+/// Decodes an upload, rotates it upright according to its EXIF orientation and encodes
+/// each of `sizes`. Only the pixels are re-encoded, so EXIF, GPS and other metadata is dropped.
+fn encode_image_variants(
+    image_data: &[u8],
+    sizes: &[ImageSize],
+) -> Result<Vec<(ImageSize, Vec<u8>)>, AppError> {
+    let mut decoder = ImageReader::new(Cursor::new(image_data))
+        .with_guessed_format()
+        .map_err(|_| AppError::bad_request(None))?
+        .into_decoder()
+        .map_err(|_| AppError::bad_request(None))?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut img = DynamicImage::from_decoder(decoder).map_err(|_| AppError::bad_request(None))?;
+    img.apply_orientation(orientation);
 
-    let webp_data = encoder.encode(75.0).to_vec();
+    sizes
+        .iter()
+        .map(|size| {
+            let max = size.max_dimension();
+            let resized = if img.width() > max || img.height() > max {
+                img.resize(max, max, FilterType::Lanczos3)
+            } else {
+                img.clone()
+            };
+            // The WebP encoder only accepts 8-bit RGB(A)
+            let resized = if resized.color().has_alpha() {
+                DynamicImage::ImageRgba8(resized.to_rgba8())
+            } else {
+                DynamicImage::ImageRgb8(resized.to_rgb8())
+            };
+            let encoder = Encoder::from_image(&resized).map_err(|e| {
+                error!("WebP encoding error: {}", e);
+                AppError::internal_error("Fail to save image".to_string())
+            })?;
+            Ok((*size, encoder.encode(75.0).to_vec()))
+        })
+        .collect()
+}
 
-    tokio::fs::write(&path, webp_data).await.map_err(|e| {
-        error!("Fail to write image file: {}", e);
-        AppError::internal_error("Fail to save product image".to_string())
-    })?;
+/// Deletes files under `uploads/products` that no product references any more,
+/// such as images replaced by an upload or left behind by a purged product.
+/// Recent files are kept as they may belong to a product that is still being created.
+pub async fn remove_orphaned_product_images(referenced: &[String]) {
+    let upload_dir = Path::new("uploads/products");
+    let mut entries = match tokio::fs::read_dir(upload_dir).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("Failed to read product uploads: {}", e);
+            return;
+        }
+    };
 
-    Ok(filename)
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = format!("uploads/products/{}", entry.file_name().to_string_lossy());
+        if referenced.contains(&path) {
+            continue;
+        }
+        let is_recent = entry
+            .metadata()
+            .await
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_none_or(|age| age < ORPHAN_GRACE_PERIOD);
+        if is_recent {
+            continue;
+        }
+        match tokio::fs::remove_file(entry.path()).await {
+            Ok(()) => debug!("Removed orphaned product image {}", path),
+            Err(e) => error!("Failed to remove orphaned product image {}: {}", path, e),
+        }
+    }
 }
@@ -5,6 +5,7 @@ use axum::extract::Request;
 use axum::middleware::Next;
 
 use crate::helper::validate_token;
+use crate::paseto::TokenPurpose;
 use axum_casbin::CasbinVals;
 use axum_extra::headers::authorization::Bearer;
 use axum_extra::headers::Authorization;
@@ -24,7 +25,7 @@ pub async fn authentication_middleware(
     let mut claims: Option<Claims> = None;
     match bearer {
         Ok(TypedHeader(Authorization(bearer))) => {
-            if let Some((role, c)) = validate_token(bearer.token()) {
+            if let Some((role, c)) = validate_token(bearer.token(), TokenPurpose::Access) {
                 let vals = CasbinVals {
                     subject: role,
                     domain: None,
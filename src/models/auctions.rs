@@ -0,0 +1,47 @@
+use crate::schema::private;
+use chrono::NaiveDateTime;
+use diesel::{Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(
+    Debug, Serialize, Deserialize, Default, Copy, Clone, PartialEq, diesel_derive_enum::DbEnum,
+)]
+#[ExistingTypePath = "private::sql_types::AuctionStatus"]
+pub enum AuctionStatus {
+    #[default]
+    Open,
+    Settled,
+    Unsold,
+    Cancelled,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, Selectable)]
+#[diesel(table_name = private::auctions)]
+pub struct Auction {
+    pub uuid: Uuid,
+    pub title: String,
+    pub description: String,
+    /// Lowest winning bid, bids below it are accepted but the item goes unsold
+    pub reserve_price: i32,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: NaiveDateTime,
+    pub status: AuctionStatus,
+    pub high_bid: Option<i32>,
+    /// Whoever currently has `high_bid` held in their wallet
+    pub high_bidder: Option<Uuid>,
+    pub created_by: Uuid,
+    pub settled_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, Selectable)]
+#[diesel(table_name = private::auction_bids)]
+pub struct AuctionBid {
+    pub id: i64,
+    pub auction_uuid: Uuid,
+    pub user_uuid: Uuid,
+    pub amount: i32,
+    pub created_at: NaiveDateTime,
+}
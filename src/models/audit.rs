@@ -0,0 +1,109 @@
+use crate::schema::private;
+use chrono::NaiveDateTime;
+use diesel::{Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+pub enum AuditAction {
+    UserCreated,
+    UserUpdated,
+    UserDeleted,
+    UserSuspended,
+    UserActivated,
+    UserPasswordReset,
+    WalletCredited,
+    WalletDebited,
+    ProductCreated,
+    ProductUpdated,
+    ProductImageUpdated,
+    ProductStockAdjusted,
+    ProductArchived,
+    ProductRestored,
+    ProductPurged,
+    CategoryCreated,
+    CategoryUpdated,
+    CategoryDeleted,
+    TagCreated,
+    TagUpdated,
+    TagDeleted,
+    OrderApproved,
+    OrderFulfilled,
+    OrderCancelled,
+    PolicyAdded,
+    PolicyRemoved,
+    TaskCreated,
+    TaskClosed,
+    TaskSubmissionApproved,
+    TaskSubmissionRejected,
+    ProductRequestApproved,
+    ProductRequestRejected,
+    ProductRequestFulfilled,
+    AuctionCreated,
+    AuctionCancelled,
+    CreditRuleCreated,
+    CreditRulePaused,
+    CreditRuleResumed,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::UserCreated => "user.created",
+            AuditAction::UserUpdated => "user.updated",
+            AuditAction::UserDeleted => "user.deleted",
+            AuditAction::UserSuspended => "user.suspended",
+            AuditAction::UserActivated => "user.activated",
+            AuditAction::UserPasswordReset => "user.password_reset",
+            AuditAction::WalletCredited => "wallet.credited",
+            AuditAction::WalletDebited => "wallet.debited",
+            AuditAction::ProductCreated => "product.created",
+            AuditAction::ProductUpdated => "product.updated",
+            AuditAction::ProductImageUpdated => "product.image_updated",
+            AuditAction::ProductStockAdjusted => "product.stock_adjusted",
+            AuditAction::ProductArchived => "product.archived",
+            AuditAction::ProductRestored => "product.restored",
+            AuditAction::ProductPurged => "product.purged",
+            AuditAction::CategoryCreated => "category.created",
+            AuditAction::CategoryUpdated => "category.updated",
+            AuditAction::CategoryDeleted => "category.deleted",
+            AuditAction::TagCreated => "tag.created",
+            AuditAction::TagUpdated => "tag.updated",
+            AuditAction::TagDeleted => "tag.deleted",
+            AuditAction::OrderApproved => "order.approved",
+            AuditAction::OrderFulfilled => "order.fulfilled",
+            AuditAction::OrderCancelled => "order.cancelled",
+            AuditAction::PolicyAdded => "policy.added",
+            AuditAction::PolicyRemoved => "policy.removed",
+            AuditAction::TaskCreated => "task.created",
+            AuditAction::TaskClosed => "task.closed",
+            AuditAction::TaskSubmissionApproved => "task.submission_approved",
+            AuditAction::TaskSubmissionRejected => "task.submission_rejected",
+            AuditAction::ProductRequestApproved => "product_request.approved",
+            AuditAction::ProductRequestRejected => "product_request.rejected",
+            AuditAction::ProductRequestFulfilled => "product_request.fulfilled",
+            AuditAction::AuctionCreated => "auction.created",
+            AuditAction::AuctionCancelled => "auction.cancelled",
+            AuditAction::CreditRuleCreated => "credit_rule.created",
+            AuditAction::CreditRulePaused => "credit_rule.paused",
+            AuditAction::CreditRuleResumed => "credit_rule.resumed",
+        }
+    }
+
+    pub fn target_type(&self) -> &'static str {
+        self.as_str().split('.').next().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, Selectable)]
+#[diesel(table_name = private::audit_log)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub actor_uuid: Uuid,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub created_at: NaiveDateTime,
+}
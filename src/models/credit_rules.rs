@@ -0,0 +1,40 @@
+use crate::schema::private;
+use chrono::NaiveDateTime;
+use diesel::{Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(
+    Debug, Serialize, Deserialize, Default, Copy, Clone, PartialEq, diesel_derive_enum::DbEnum,
+)]
+#[ExistingTypePath = "private::sql_types::CreditRuleKind"]
+pub enum CreditRuleKind {
+    /// Paid every month on `day_of_month`
+    #[default]
+    Monthly,
+    /// Paid once a year on each resident's date of birth
+    Birthday,
+    /// Paid a single time at `run_at`
+    Once,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, Selectable)]
+#[diesel(table_name = private::credit_rules)]
+pub struct CreditRule {
+    pub uuid: Uuid,
+    pub name: String,
+    pub kind: CreditRuleKind,
+    pub amount: i32,
+    pub day_of_month: Option<i32>,
+    pub run_at: Option<NaiveDateTime>,
+    /// Only residents of this school, if set
+    pub school: Option<String>,
+    /// Only residents living in this bunk, if set
+    pub bunk: Option<String>,
+    pub paused_at: Option<NaiveDateTime>,
+    pub created_by: Uuid,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    /// Points paid by the rule lapse this many days after they are credited
+    pub expires_in_days: Option<i32>,
+}
@@ -1,3 +1,11 @@
+pub mod auctions;
+pub mod audit;
+pub mod credit_rules;
+pub mod orders;
+pub mod policy;
+pub mod product_requests;
 pub mod products;
+pub mod stock;
+pub mod tasks;
 pub mod user;
 pub mod wallet;
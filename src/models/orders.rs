@@ -0,0 +1,38 @@
+use crate::schema::private;
+use chrono::NaiveDateTime;
+use diesel::{Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(
+    Debug, Serialize, Deserialize, Default, Copy, Clone, PartialEq, diesel_derive_enum::DbEnum,
+)]
+#[ExistingTypePath = "private::sql_types::OrderStatus"]
+pub enum OrderStatus {
+    #[default]
+    Pending,
+    Approved,
+    Fulfilled,
+    Cancelled,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, Selectable)]
+#[diesel(table_name = private::orders)]
+pub struct Order {
+    pub uuid: Uuid,
+    pub user_uuid: Uuid,
+    pub status: OrderStatus,
+    pub total_cost: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, Selectable)]
+#[diesel(table_name = private::order_items)]
+pub struct OrderItem {
+    pub id: i32,
+    pub order_uuid: Uuid,
+    pub product_uuid: Uuid,
+    pub quantity: i32,
+    pub unit_cost: i32,
+}
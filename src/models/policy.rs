@@ -0,0 +1,60 @@
+use crate::schema::private;
+use diesel::{Insertable, Queryable, Selectable};
+
+#[derive(Debug, Clone, Queryable, Selectable)]
+#[diesel(table_name = private::casbin_rules)]
+pub struct PolicyRule {
+    pub ptype: String,
+    pub v0: String,
+    pub v1: String,
+    pub v2: String,
+    pub v3: String,
+    pub v4: String,
+    pub v5: String,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = private::casbin_rules)]
+pub struct NewPolicyRule {
+    pub ptype: String,
+    pub v0: String,
+    pub v1: String,
+    pub v2: String,
+    pub v3: String,
+    pub v4: String,
+    pub v5: String,
+}
+
+impl PolicyRule {
+    /// Returns the rule values with the unused trailing columns dropped.
+    pub fn values(&self) -> Vec<String> {
+        let mut values = vec![
+            self.v0.clone(),
+            self.v1.clone(),
+            self.v2.clone(),
+            self.v3.clone(),
+            self.v4.clone(),
+            self.v5.clone(),
+        ];
+        while values.last().is_some_and(|value| value.is_empty()) {
+            values.pop();
+        }
+        values
+    }
+}
+
+impl NewPolicyRule {
+    pub fn new(ptype: &str, rule: Vec<String>) -> Self {
+        let mut values = rule.into_iter();
+        let mut next = || values.next().unwrap_or_default();
+        NewPolicyRule {
+            ptype: ptype.to_string(),
+            v0: next(),
+            v1: next(),
+            v2: next(),
+            v3: next(),
+            v4: next(),
+            v5: next(),
+        }
+    }
+}
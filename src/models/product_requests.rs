@@ -0,0 +1,35 @@
+use crate::schema::private;
+use chrono::NaiveDateTime;
+use diesel::{Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(
+    Debug, Serialize, Deserialize, Default, Copy, Clone, PartialEq, diesel_derive_enum::DbEnum,
+)]
+#[ExistingTypePath = "private::sql_types::ProductRequestStatus"]
+pub enum ProductRequestStatus {
+    #[default]
+    Open,
+    /// Staff intend to source the item
+    Approved,
+    Rejected,
+    /// The item has been added to the catalogue
+    Fulfilled,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, Selectable)]
+#[diesel(table_name = private::product_requests)]
+pub struct ProductRequest {
+    pub uuid: Uuid,
+    pub user_uuid: Uuid,
+    pub title: String,
+    pub description: String,
+    pub quantity: i32,
+    pub status: ProductRequestStatus,
+    pub review_reason: Option<String>,
+    pub reviewed_by: Option<Uuid>,
+    pub product_uuid: Option<Uuid>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
@@ -1,4 +1,6 @@
-use diesel::Queryable;
+use crate::schema::private;
+use chrono::NaiveDateTime;
+use diesel::{AsChangeset, Insertable, Queryable, Selectable};
 use diesel_full_text_search::TsVector;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -13,16 +15,138 @@ pub struct ProductDB {
     pub description: String,
     pub stock: i32,
     pub cost: i32,
+    pub category_id: Option<i32>,
+    pub search_terms: String,
     #[diesel(sql_type = TsVector)]
     pub search_vector: TsVector,
+    pub archived_at: Option<NaiveDateTime>,
+    pub reorder_threshold: i32,
+    pub low_stock_alerted_at: Option<NaiveDateTime>,
+    pub card_path: String,
+    pub thumbnail_path: String,
 }
 
-#[derive(Queryable, Serialize, Deserialize, Clone, Debug)]
+/// The product columns returned by the API, tags are loaded separately.
+#[derive(Queryable, Selectable, Clone, Debug)]
+#[diesel(table_name = private::products)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ProductRecord {
+    pub uuid: Uuid,
+    pub title: String,
+    pub image_path: String,
+    pub description: String,
+    pub stock: i32,
+    pub cost: i32,
+    pub category_id: Option<i32>,
+    pub archived_at: Option<NaiveDateTime>,
+    pub reorder_threshold: i32,
+    pub card_path: String,
+    pub thumbnail_path: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Product {
     pub uuid: Uuid,
     pub title: String,
     pub image_path: String,
+    /// Smaller variants of `image_path` for grids and lists
+    pub card_path: String,
+    pub thumbnail_path: String,
     pub description: String,
     pub stock: i32,
     pub cost: i32,
+    pub category_id: Option<i32>,
+    pub tags: Vec<String>,
+    /// Archived products are hidden from the catalogue but kept for order history
+    pub archived_at: Option<NaiveDateTime>,
+    /// Staff are alerted once stock falls to or below this level
+    pub reorder_threshold: i32,
+}
+
+impl Product {
+    pub fn new(record: ProductRecord, tags: Vec<String>) -> Self {
+        Product {
+            uuid: record.uuid,
+            title: record.title,
+            image_path: record.image_path,
+            card_path: record.card_path,
+            thumbnail_path: record.thumbnail_path,
+            description: record.description,
+            stock: record.stock,
+            cost: record.cost,
+            category_id: record.category_id,
+            tags,
+            archived_at: record.archived_at,
+            reorder_threshold: record.reorder_threshold,
+        }
+    }
+}
+
+/// Stored variants of a product photo, each capped at `ImageSize::max_dimension`.
+#[derive(Insertable, AsChangeset, Serialize, Deserialize, Default, Clone, Debug)]
+#[diesel(table_name = private::products)]
+pub struct ProductImage {
+    #[diesel(column_name = image_path)]
+    pub full: String,
+    #[diesel(column_name = card_path)]
+    pub card: String,
+    #[diesel(column_name = thumbnail_path)]
+    pub thumbnail: String,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageSize {
+    Thumbnail,
+    Card,
+    Full,
+}
+
+impl ImageSize {
+    pub const ALL: [ImageSize; 3] = [ImageSize::Thumbnail, ImageSize::Card, ImageSize::Full];
+
+    /// Longest edge in pixels, smaller images are never upscaled
+    pub fn max_dimension(&self) -> u32 {
+        match self {
+            ImageSize::Thumbnail => 160,
+            ImageSize::Card => 480,
+            ImageSize::Full => 1600,
+        }
+    }
+
+    /// Appended to the file stem, so every variant can be found from the full size path
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            ImageSize::Thumbnail => "_thumb",
+            ImageSize::Card => "_card",
+            ImageSize::Full => "",
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = private::products)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct LowStockProduct {
+    pub uuid: Uuid,
+    pub title: String,
+    pub stock: i32,
+    pub reorder_threshold: i32,
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = private::categories)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Category {
+    pub id: i32,
+    pub name: String,
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = private::tags)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Tag {
+    pub id: i32,
+    pub name: String,
 }
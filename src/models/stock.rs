@@ -0,0 +1,39 @@
+use crate::schema::private;
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, diesel_derive_enum::DbEnum)]
+#[ExistingTypePath = "private::sql_types::StockMovementKind"]
+pub enum StockMovementKind {
+    Restock,
+    Redemption,
+    Adjustment,
+    WriteOff,
+    Cancellation,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, Selectable)]
+#[diesel(table_name = private::stock_movements)]
+pub struct StockMovement {
+    pub id: i64,
+    pub product_uuid: Uuid,
+    pub actor_uuid: Option<Uuid>,
+    pub kind: StockMovementKind,
+    pub delta: i32,
+    pub reason: Option<String>,
+    pub order_uuid: Option<Uuid>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = private::stock_movements)]
+pub struct NewStockMovement {
+    pub product_uuid: Uuid,
+    pub actor_uuid: Option<Uuid>,
+    pub kind: StockMovementKind,
+    pub delta: i32,
+    pub reason: Option<String>,
+    pub order_uuid: Option<Uuid>,
+}
@@ -0,0 +1,50 @@
+use crate::schema::private;
+use chrono::NaiveDateTime;
+use diesel::{Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(
+    Debug, Serialize, Deserialize, Default, Copy, Clone, PartialEq, diesel_derive_enum::DbEnum,
+)]
+#[ExistingTypePath = "private::sql_types::SubmissionStatus"]
+pub enum SubmissionStatus {
+    #[default]
+    Claimed,
+    Submitted,
+    Approved,
+    Rejected,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, Selectable)]
+#[diesel(table_name = private::tasks)]
+pub struct Task {
+    pub uuid: Uuid,
+    pub title: String,
+    pub description: String,
+    /// Points credited to the resident when their submission is approved
+    pub reward: i32,
+    /// How many residents can hold a claim that has not been rejected
+    pub capacity: i32,
+    pub deadline: NaiveDateTime,
+    pub created_by: Uuid,
+    pub closed_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, Selectable)]
+#[diesel(table_name = private::task_submissions)]
+pub struct TaskSubmission {
+    pub uuid: Uuid,
+    pub task_uuid: Uuid,
+    pub user_uuid: Uuid,
+    pub status: SubmissionStatus,
+    pub note: Option<String>,
+    pub photo_path: Option<String>,
+    pub review_reason: Option<String>,
+    pub reviewed_by: Option<Uuid>,
+    pub claimed_at: NaiveDateTime,
+    pub submitted_at: Option<NaiveDateTime>,
+    pub reviewed_at: Option<NaiveDateTime>,
+}
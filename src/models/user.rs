@@ -3,12 +3,16 @@ use diesel::{Queryable, Selectable};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize, Default, Copy, Clone, diesel_derive_enum::DbEnum)]
+#[derive(
+    Debug, Serialize, Deserialize, Default, Copy, Clone, PartialEq, diesel_derive_enum::DbEnum,
+)]
 #[ExistingTypePath = "private::sql_types::AccountType"]
 pub enum AccountType {
     #[default]
     User,
     Admin,
+    Volunteer,
+    Auditor,
 }
 
 impl TryFrom<&str> for AccountType {
@@ -18,6 +22,8 @@ impl TryFrom<&str> for AccountType {
         match value {
             "Admin" => Ok(AccountType::Admin),
             "User" => Ok(AccountType::User),
+            "Volunteer" => Ok(AccountType::Volunteer),
+            "Auditor" => Ok(AccountType::Auditor),
             _ => Err(format!("Unknown role: {}", value)),
         }
     }
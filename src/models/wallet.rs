@@ -11,6 +11,8 @@ pub struct Wallet {
     pub user_uuid: Uuid,
     pub balance: i32,
     pub updated_at: NaiveDateTime,
+    /// Part of the balance reserved for leading auction bids
+    pub held: i32,
 }
 #[derive(Debug, Serialize, Deserialize, Default, Copy, Clone, diesel_derive_enum::DbEnum)]
 #[ExistingTypePath = "private::sql_types::TransactionType"]
@@ -18,6 +20,8 @@ pub enum TransactionType {
     #[default]
     Debit,
     Credit,
+    /// Points removed because their credit lot lapsed
+    Expiry,
 }
 
 impl TryFrom<&str> for TransactionType {
@@ -27,6 +31,7 @@ impl TryFrom<&str> for TransactionType {
         match value {
             "Debit" => Ok(TransactionType::Debit),
             "Credit" => Ok(TransactionType::Credit),
+            "Expiry" => Ok(TransactionType::Expiry),
             _ => Err(format!("Unknown transaction type: {}", value)),
         }
     }
@@ -42,3 +47,15 @@ pub struct Transaction {
     pub description: String,
     pub created_at: NaiveDateTime,
 }
+
+/// Points from a single credit. Debits use up the oldest lots first.
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, Selectable)]
+#[diesel(table_name = private::credit_lots)]
+pub struct CreditLot {
+    pub id: i64,
+    pub wallet_id: i32,
+    pub amount: i32,
+    pub remaining: i32,
+    pub expires_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
@@ -0,0 +1,87 @@
+pub mod outbox;
+pub mod queue;
+pub mod sms;
+pub mod smtp;
+pub mod templates;
+
+use crate::notifications::outbox::OutboxNotifier;
+use crate::notifications::sms::HttpSmsNotifier;
+use crate::notifications::smtp::SmtpNotifier;
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum Recipient {
+    Email(String),
+    Sms(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Message {
+    pub recipient: Recipient,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn send(&self, message: &Message) -> anyhow::Result<()>;
+}
+
+/// Routes each message to the backend configured for its channel.
+#[derive(Clone)]
+pub struct Notifiers {
+    email: Arc<dyn Notifier>,
+    sms: Arc<dyn Notifier>,
+}
+
+impl Notifiers {
+    /// Uses SMTP when `SMTP_HOST` is set and the HTTP SMS gateway when `SMS_API_URL` is set.
+    /// The outbox writes messages, passwords included, to disk in plain text, so a channel
+    /// that is not configured only falls back to it in dev mode or with `NOTIFY_OUTBOX=true`.
+    pub fn from_env(dev_mode: bool) -> anyhow::Result<Self> {
+        let allow_outbox =
+            dev_mode || std::env::var("NOTIFY_OUTBOX").unwrap_or("false".to_string()) == "true";
+        let outbox = |channel: &str| -> anyhow::Result<Arc<dyn Notifier>> {
+            if !allow_outbox {
+                return Err(anyhow!(
+                    "{} delivery is not configured, set it up or set NOTIFY_OUTBOX=true",
+                    channel
+                ));
+            }
+            warn!(
+                "{} delivery is not configured, writing to the outbox",
+                channel
+            );
+            Ok(Arc::new(OutboxNotifier::from_env()))
+        };
+
+        let email: Arc<dyn Notifier> = match SmtpNotifier::from_env() {
+            Some(smtp) => Arc::new(smtp.context("Invalid SMTP configuration")?),
+            None => outbox("Email")?,
+        };
+        let sms: Arc<dyn Notifier> = match HttpSmsNotifier::from_env() {
+            Some(sms) => Arc::new(sms),
+            None => outbox("SMS")?,
+        };
+
+        info!(
+            "Notifications: email via {}, sms via {}",
+            email.name(),
+            sms.name()
+        );
+        Ok(Notifiers { email, sms })
+    }
+
+    pub async fn send(&self, message: &Message) -> anyhow::Result<()> {
+        match message.recipient {
+            Recipient::Email(_) => self.email.send(message).await,
+            Recipient::Sms(_) => self.sms.send(message).await,
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use crate::notifications::{Message, Notifier};
+use async_trait::async_trait;
+use chrono::Utc;
+use log::info;
+use serde_json::json;
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+
+/// Development backend that appends every message to `outbox/notifications.log`
+/// as JSON lines instead of delivering it.
+pub struct OutboxNotifier {
+    dir: PathBuf,
+}
+
+impl OutboxNotifier {
+    pub fn from_env() -> Self {
+        let dir = std::env::var("NOTIFY_OUTBOX_DIR").unwrap_or_else(|_| "outbox".to_string());
+        OutboxNotifier {
+            dir: PathBuf::from(dir),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for OutboxNotifier {
+    fn name(&self) -> &'static str {
+        "outbox"
+    }
+
+    async fn send(&self, message: &Message) -> anyhow::Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let entry = json!({
+            "sent_at": Utc::now(),
+            "recipient": message.recipient,
+            "subject": message.subject,
+            "body": message.body,
+        });
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join("notifications.log"))
+            .await?;
+        file.write_all(format!("{}\n", entry).as_bytes()).await?;
+
+        info!(
+            "Outbox message to {:?}: {}",
+            message.recipient, message.subject
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use crate::models::user::User;
+use crate::notifications::templates::Template;
+use crate::notifications::{Message, Notifiers, Recipient};
+use crate::req_res::AppError;
+use crate::utils::{deserialize_from_messagepack, serialize_to_messagepack};
+use chrono::Utc;
+use fred::prelude::*;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use uuid::Uuid;
+
+const QUEUE_KEY: &str = "notifications:queue";
+const RETRY_KEY: &str = "notifications:retry";
+const DEAD_KEY: &str = "notifications:dead";
+const MAX_ATTEMPTS: u32 = 5;
+/// Replaces the body of dead letters, which can hold passwords and OTPs.
+const REDACTED_BODY: &str = "[redacted]";
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct QueuedMessage {
+    id: Uuid,
+    attempts: u32,
+    message: Message,
+}
+
+/// Queues a message for delivery by the background worker so request handlers never
+/// wait on an SMTP server or SMS gateway.
+pub async fn enqueue(redis: &Client, message: Message) -> Result<(), AppError> {
+    let queued = QueuedMessage {
+        id: Uuid::new_v4(),
+        attempts: 0,
+        message,
+    };
+    redis
+        .lpush::<(), _, _>(QUEUE_KEY, serialize_to_messagepack(&queued))
+        .await?;
+    Ok(())
+}
+
+/// Queues the rendered template for both the user's email address and phone number.
+pub async fn notify_user(
+    redis: &Client,
+    user: &User,
+    template: &Template<'_>,
+) -> Result<(), AppError> {
+    enqueue(redis, template.render(Recipient::Email(user.email.clone()))).await?;
+    enqueue(redis, template.render(Recipient::Sms(user.phone.clone()))).await?;
+    Ok(())
+}
+
+/// Delivers queued messages until the process exits. Failed deliveries are retried with
+/// exponential backoff and moved to a dead letter list after `MAX_ATTEMPTS`.
+pub async fn run_worker(redis: Client, notifiers: Notifiers) {
+    loop {
+        if let Err(e) = promote_due_retries(&redis).await {
+            error!("Unable to promote notification retries: {}", e);
+        }
+        match redis.rpop::<Option<Vec<u8>>, _>(QUEUE_KEY, None).await {
+            Ok(Some(data)) => process(&redis, &notifiers, &data).await,
+            Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+            Err(e) => {
+                error!("Unable to read notification queue: {}", e);
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+async fn process(redis: &Client, notifiers: &Notifiers, data: &[u8]) {
+    let mut queued: QueuedMessage = match deserialize_from_messagepack(data) {
+        Ok(queued) => queued,
+        Err(e) => {
+            error!("Dropping undecodable notification: {}", e);
+            return;
+        }
+    };
+
+    match notifiers.send(&queued.message).await {
+        Ok(()) => info!("Delivered notification {}", queued.id),
+        Err(e) => {
+            queued.attempts += 1;
+            let result = if queued.attempts >= MAX_ATTEMPTS {
+                error!(
+                    "Giving up on notification {} after {} attempts: {}",
+                    queued.id, queued.attempts, e
+                );
+                queued.message.body = REDACTED_BODY.to_string();
+                redis
+                    .lpush::<(), _, _>(DEAD_KEY, serialize_to_messagepack(&queued))
+                    .await
+            } else {
+                let delay = 2_i64.pow(queued.attempts) * 15;
+                warn!(
+                    "Notification {} failed (attempt {}), retrying in {}s: {}",
+                    queued.id, queued.attempts, delay, e
+                );
+                let due = (Utc::now().timestamp() + delay) as f64;
+                let packed = serialize_to_messagepack(&queued);
+                redis
+                    .zadd::<(), _, _>(RETRY_KEY, None, None, false, false, (due, packed))
+                    .await
+            };
+            if let Err(e) = result {
+                error!("Unable to requeue notification {}: {}", queued.id, e);
+            }
+        }
+    }
+}
+
+async fn promote_due_retries(redis: &Client) -> Result<(), Error> {
+    let now = Utc::now().timestamp() as f64;
+    let due: Vec<Vec<u8>> = redis
+        .zrangebyscore(RETRY_KEY, f64::NEG_INFINITY, now, false, None)
+        .await?;
+    for entry in due {
+        // Only the worker that removes the entry gets to requeue it
+        let removed: i64 = redis.zrem(RETRY_KEY, entry.clone()).await?;
+        if removed == 1 {
+            redis.lpush::<(), _, _>(QUEUE_KEY, entry).await?;
+        }
+    }
+    Ok(())
+}
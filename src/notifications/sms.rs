@@ -0,0 +1,59 @@
+use crate::notifications::{Message, Notifier, Recipient};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use serde_json::json;
+
+/// Delivers SMS through a JSON HTTP gateway that accepts `to`, `from` and `message`
+/// fields and authenticates with a bearer API key.
+pub struct HttpSmsNotifier {
+    client: reqwest::Client,
+    api_url: String,
+    api_key: String,
+    sender: String,
+    country_code: String,
+}
+
+impl HttpSmsNotifier {
+    /// Returns `None` when `SMS_API_URL` is not set.
+    pub fn from_env() -> Option<Self> {
+        let api_url = std::env::var("SMS_API_URL").ok()?;
+        Some(HttpSmsNotifier {
+            client: reqwest::Client::new(),
+            api_url,
+            api_key: std::env::var("SMS_API_KEY").unwrap_or_default(),
+            sender: std::env::var("SMS_SENDER").unwrap_or_else(|_| "MWH".to_string()),
+            country_code: std::env::var("SMS_COUNTRY_CODE").unwrap_or_else(|_| "+65".to_string()),
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for HttpSmsNotifier {
+    fn name(&self) -> &'static str {
+        "http-sms"
+    }
+
+    async fn send(&self, message: &Message) -> anyhow::Result<()> {
+        let Recipient::Sms(phone) = &message.recipient else {
+            return Err(anyhow!("SMS backend can only deliver text messages"));
+        };
+        // Phone numbers are stored without a country code, see AdminNewUserReq validation
+        let to = if phone.starts_with('+') {
+            phone.clone()
+        } else {
+            format!("{}{}", self.country_code, phone)
+        };
+        self.client
+            .post(&self.api_url)
+            .bearer_auth(&self.api_key)
+            .json(&json!({
+                "to": to,
+                "from": self.sender,
+                "message": message.body,
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+use crate::notifications::{Message, Notifier, Recipient};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+
+pub struct SmtpNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpNotifier {
+    /// Returns `None` when `SMTP_HOST` is not set.
+    pub fn from_env() -> Option<anyhow::Result<Self>> {
+        let host = std::env::var("SMTP_HOST").ok()?;
+        Some(Self::new(&host))
+    }
+
+    fn new(host: &str) -> anyhow::Result<Self> {
+        let from = std::env::var("SMTP_FROM")
+            .map_err(|_| anyhow!("SMTP_FROM must be set when SMTP_HOST is set"))?
+            .parse::<Mailbox>()?;
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?;
+        if let Ok(port) = std::env::var("SMTP_PORT") {
+            builder = builder.port(port.parse()?);
+        }
+        if let (Ok(username), Ok(password)) = (
+            std::env::var("SMTP_USERNAME"),
+            std::env::var("SMTP_PASSWORD"),
+        ) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+        Ok(SmtpNotifier {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for SmtpNotifier {
+    fn name(&self) -> &'static str {
+        "smtp"
+    }
+
+    async fn send(&self, message: &Message) -> anyhow::Result<()> {
+        let Recipient::Email(to) = &message.recipient else {
+            return Err(anyhow!("SMTP backend can only deliver email"));
+        };
+        let email = lettre::Message::builder()
+            .from(self.from.clone())
+            .to(to.parse()?)
+            .subject(message.subject.clone())
+            .body(message.body.clone())?;
+        self.transport.send(email).await?;
+        Ok(())
+    }
+}
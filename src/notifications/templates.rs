@@ -0,0 +1,105 @@
+use crate::models::product_requests::ProductRequestStatus;
+use crate::models::products::LowStockProduct;
+use crate::notifications::{Message, Recipient};
+
+pub enum Template<'a> {
+    PasswordResetOtp {
+        otp: &'a str,
+        expires_in_minutes: i64,
+    },
+    Welcome {
+        name: &'a str,
+        resident_id: &'a str,
+        password: &'a str,
+    },
+    PasswordReset {
+        name: &'a str,
+        password: &'a str,
+    },
+    LowStockDigest {
+        products: &'a [LowStockProduct],
+    },
+    ProductRequestReviewed {
+        name: &'a str,
+        title: &'a str,
+        status: ProductRequestStatus,
+        reason: Option<&'a str>,
+    },
+}
+
+impl Template<'_> {
+    pub fn render(&self, recipient: Recipient) -> Message {
+        let (subject, body) = match self {
+            Template::PasswordResetOtp {
+                otp,
+                expires_in_minutes,
+            } => (
+                "Your password reset code".to_string(),
+                format!(
+                    "Your Welfare Home password reset code is {}. It expires in {} minutes. If you did not request this, you can ignore this message.",
+                    otp, expires_in_minutes
+                ),
+            ),
+            Template::Welcome {
+                name,
+                resident_id,
+                password,
+            } => (
+                "Welcome to the Welfare Home minimart".to_string(),
+                format!(
+                    "Hi {}, an account has been created for you. Log in with ID {} and temporary password {}. You will be asked to choose a new password after logging in.",
+                    name, resident_id, password
+                ),
+            ),
+            Template::PasswordReset { name, password } => (
+                "Your password has been reset".to_string(),
+                format!(
+                    "Hi {}, staff have reset your password. Your temporary password is {}. You will be asked to choose a new password after logging in.",
+                    name, password
+                ),
+            ),
+            Template::LowStockDigest { products } => (
+                format!("{} products need restocking", products.len()),
+                format!(
+                    "The following products have reached their reorder threshold:\n{}",
+                    products
+                        .iter()
+                        .map(|product| format!(
+                            "- {}: {} left (threshold {})",
+                            product.title, product.stock, product.reorder_threshold
+                        ))
+                        .collect::<Vec<String>>()
+                        .join("\n")
+                ),
+            ),
+            Template::ProductRequestReviewed {
+                name,
+                title,
+                status,
+                reason,
+            } => {
+                let outcome = match status {
+                    ProductRequestStatus::Approved => {
+                        "has been approved and staff will try to source it".to_string()
+                    }
+                    ProductRequestStatus::Fulfilled => {
+                        "is now available in the minimart".to_string()
+                    }
+                    _ => format!(
+                        "could not be accepted: {}",
+                        reason.unwrap_or("no reason given")
+                    ),
+                };
+                (
+                    format!("Update on your request for {}", title),
+                    format!("Hi {}, your request for {} {}.", name, title, outcome),
+                )
+            }
+        };
+        Message {
+            recipient,
+            subject,
+            body,
+        }
+    }
+}
@@ -5,6 +5,7 @@ use pasetors::claims::Claims;
 use pasetors::keys::AsymmetricSecretKey;
 use pasetors::public;
 use pasetors::version4::V4;
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::str::FromStr;
 use uuid::Uuid;
@@ -19,39 +20,86 @@ pub fn get_private_public_keypair() -> (&'static String, &'static String) {
     (s, p)
 }
 
-pub fn generate_access_token(uuid: &str, role: &str) -> String {
+pub const TOKEN_ISSUER: &str = "h4g_backend";
+pub const TOKEN_AUDIENCE: &str = "h4g_welfare_home";
+pub const REFRESH_TOKEN_LIFETIME_DAYS: i64 = 14;
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+pub enum TokenPurpose {
+    Access,
+    Refresh,
+}
+
+impl TokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenPurpose::Access => "access",
+            TokenPurpose::Refresh => "refresh",
+        }
+    }
+}
+
+impl TryFrom<&str> for TokenPurpose {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "access" => Ok(TokenPurpose::Access),
+            "refresh" => Ok(TokenPurpose::Refresh),
+            _ => Err(format!("Unknown token purpose: {}", value)),
+        }
+    }
+}
+
+fn generate_token(
+    uuid: &str,
+    role: &str,
+    purpose: TokenPurpose,
+    jti: Uuid,
+    lifetime: chrono::Duration,
+) -> String {
     let (secret_key, _) = get_private_public_keypair();
     let sk = AsymmetricSecretKey::<V4>::try_from(secret_key.as_str()).unwrap();
     let mut claims = Claims::new().unwrap();
-    let now = Utc::now();
-    let expiration_time = now + chrono::Duration::try_minutes(5).unwrap();
+    let expiration_time = Utc::now() + lifetime;
     let expiration_string = expiration_time.to_rfc3339();
 
+    claims.issuer(TOKEN_ISSUER).unwrap();
+    claims.audience(TOKEN_AUDIENCE).unwrap();
+    claims.token_identifier(&jti.to_string()).unwrap();
     claims.add_additional("user_uid", uuid.to_string()).unwrap();
-
     claims.add_additional("role", role.to_string()).unwrap();
+    claims.add_additional("purpose", purpose.as_str()).unwrap();
     claims.expiration(&expiration_string).unwrap();
     public::sign(&sk, &claims, None, None).unwrap()
 }
 
-pub fn generate_refresh_token(uuid: &str, role: &str) -> String {
-    let (secret_key, _) = get_private_public_keypair();
-    let sk = AsymmetricSecretKey::<V4>::try_from(secret_key.as_str()).unwrap();
-    let now = Utc::now();
-    let expiration_time = now + chrono::Duration::try_days(14).unwrap();
-    let expiration_string = expiration_time.to_rfc3339();
-    let mut claims = Claims::new().unwrap();
-    claims.add_additional("user_uid", uuid.to_string()).unwrap();
-    claims.add_additional("role", role.to_string()).unwrap();
-    claims.expiration(&expiration_string).unwrap();
+pub fn generate_access_token(uuid: &str, role: &str) -> String {
+    generate_token(
+        uuid,
+        role,
+        TokenPurpose::Access,
+        Uuid::new_v4(),
+        chrono::Duration::try_minutes(5).unwrap(),
+    )
+}
 
-    public::sign(&sk, &claims, None, None).unwrap()
+pub fn generate_refresh_token(uuid: &str, role: &str, jti: Uuid) -> String {
+    generate_token(
+        uuid,
+        role,
+        TokenPurpose::Refresh,
+        jti,
+        chrono::Duration::try_days(REFRESH_TOKEN_LIFETIME_DAYS).unwrap(),
+    )
 }
 
 #[derive(Debug, Clone)]
 pub struct AuthTokenClaims {
     pub user_uid: Uuid,
     pub role: AccountType,
+    pub purpose: TokenPurpose,
+    pub jti: Uuid,
 }
 
 impl TryFrom<&Claims> for AuthTokenClaims {
@@ -74,6 +122,27 @@ impl TryFrom<&Claims> for AuthTokenClaims {
 
         let role = AccountType::try_from(role_str)?;
 
-        Ok(Self { user_uid, role })
+        let purpose_str = claims
+            .get_claim("purpose")
+            .ok_or_else(|| "Unable to deserialize 'purpose'".to_string())?
+            .as_str()
+            .ok_or_else(|| "Expected a string for 'purpose'".to_string())?;
+
+        let purpose = TokenPurpose::try_from(purpose_str)?;
+
+        let jti = claims
+            .get_claim("jti")
+            .ok_or_else(|| "Unable to deserialize 'jti'".to_string())?
+            .as_str()
+            .ok_or_else(|| "Expected a string for 'jti'".to_string())?;
+
+        let jti = Uuid::from_str(jti).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            user_uid,
+            role,
+            purpose,
+            jti,
+        })
     }
 }
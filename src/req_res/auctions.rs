@@ -0,0 +1,146 @@
+use crate::models::auctions::{Auction, AuctionBid, AuctionStatus};
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use crate::schema::private;
+use chrono::{NaiveDateTime, Utc};
+use diesel::Insertable;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct NewAuctionReq {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub reserve_price: i32,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = private::auctions)]
+pub struct NewAuction {
+    pub title: String,
+    pub description: String,
+    pub reserve_price: i32,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PlaceBidReq {
+    pub amount: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlaceBid {
+    pub amount: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuctionQueryParams {
+    pub status: Option<AuctionStatus>,
+}
+
+/// What residents see of an auction. The reserve price and the other
+/// bidders are kept private.
+#[derive(Debug, Serialize, Clone)]
+pub struct AuctionRes {
+    pub uuid: Uuid,
+    pub title: String,
+    pub description: String,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: NaiveDateTime,
+    pub status: AuctionStatus,
+    pub high_bid: Option<i32>,
+    pub reserve_met: bool,
+    /// Whether the caller currently holds the high bid
+    pub leading: bool,
+}
+
+impl AuctionRes {
+    pub fn new(auction: Auction, user: Uuid) -> AuctionRes {
+        AuctionRes {
+            reserve_met: auction
+                .high_bid
+                .is_some_and(|bid| bid >= auction.reserve_price),
+            leading: auction.high_bidder == Some(user),
+            uuid: auction.uuid,
+            title: auction.title,
+            description: auction.description,
+            starts_at: auction.starts_at,
+            ends_at: auction.ends_at,
+            status: auction.status,
+            high_bid: auction.high_bid,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct BidRes {
+    pub amount: i32,
+    pub created_at: NaiveDateTime,
+    /// Whether the bid was placed by the caller
+    pub mine: bool,
+}
+
+impl BidRes {
+    pub fn new(bid: AuctionBid, user: Uuid) -> BidRes {
+        BidRes {
+            amount: bid.amount,
+            created_at: bid.created_at,
+            mine: bid.user_uuid == user,
+        }
+    }
+}
+
+impl TryInto<NewAuction> for NewAuctionReq {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<NewAuction, Self::Error> {
+        let mut errors = vec![];
+        let title = self.title.trim().to_string();
+
+        if title.is_empty() {
+            errors.push("Title is required".to_string());
+        }
+        if self.reserve_price < 0 {
+            errors.push("Reserve price cannot be negative".to_string());
+        }
+        if self.ends_at <= self.starts_at {
+            errors.push("Auction must end after it starts".to_string());
+        }
+        if self.ends_at <= Utc::now().naive_utc() {
+            errors.push("End time must be in the future".to_string());
+        }
+
+        if errors.is_empty() {
+            Ok(NewAuction {
+                title,
+                description: self.description.trim().to_string(),
+                reserve_price: self.reserve_price,
+                starts_at: self.starts_at,
+                ends_at: self.ends_at,
+            })
+        } else {
+            Err(AppError::bad_request::<ClientErrorMessages>(
+                DataValidationError { errors }.into(),
+            ))
+        }
+    }
+}
+
+impl TryInto<PlaceBid> for PlaceBidReq {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<PlaceBid, Self::Error> {
+        if self.amount <= 0 {
+            let errors = vec!["Bid must be greater than 0".to_string()];
+            return Err(AppError::bad_request::<ClientErrorMessages>(
+                DataValidationError { errors }.into(),
+            ));
+        }
+        Ok(PlaceBid {
+            amount: self.amount,
+        })
+    }
+}
@@ -0,0 +1,71 @@
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError, MAX_PAGE};
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::Deserialize;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AuditQueryParams {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    pub actor: Option<Uuid>,
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditQuery {
+    pub page: i64,
+    pub per_page: i64,
+    pub actor: Option<Uuid>,
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
+
+impl TryInto<AuditQuery> for AuditQueryParams {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<AuditQuery, Self::Error> {
+        let mut errors = vec![];
+        let page = self.page.unwrap_or(1);
+        let per_page = self.per_page.unwrap_or(50);
+
+        if !(1..=MAX_PAGE).contains(&page) {
+            errors.push(format!("Page must be between 1 and {}", MAX_PAGE));
+        }
+        if !(1..=100).contains(&per_page) {
+            errors.push("Page size must be between 1 and 100".to_string());
+        }
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from > to {
+                errors.push("Start date must not be after end date".to_string());
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(AuditQuery {
+                page,
+                per_page,
+                actor: self.actor,
+                action: self.action,
+                target_type: self.target_type,
+                target_id: self.target_id,
+                from: self.from.and_then(|d| d.and_hms_opt(0, 0, 0)),
+                // The end date is inclusive, so filter on the start of the following day
+                to: self
+                    .to
+                    .and_then(|d| d.succ_opt())
+                    .and_then(|d| d.and_hms_opt(0, 0, 0)),
+            })
+        } else {
+            Err(AppError::bad_request::<ClientErrorMessages>(
+                DataValidationError { errors }.into(),
+            ))
+        }
+    }
+}
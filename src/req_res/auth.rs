@@ -90,10 +90,13 @@ pub struct NewTokens {
 }
 
 impl NewTokens {
-    pub(crate) fn new(uuid: Uuid, role: AccountType) -> Self {
+    pub(crate) fn new(uuid: Uuid, role: AccountType, refresh_jti: Uuid) -> Self {
         let access_token = generate_access_token(&uuid.to_string(), format!("{:?}", role).as_str());
-        let refresh_token =
-            generate_refresh_token(&uuid.to_string(), format!("{:?}", role).as_str());
+        let refresh_token = generate_refresh_token(
+            &uuid.to_string(),
+            format!("{:?}", role).as_str(),
+            refresh_jti,
+        );
         NewTokens {
             access_token,
             refresh_token,
@@ -118,16 +121,13 @@ impl Into<RedactedUser> for User {
     }
 }
 
-impl Into<UserAuthenticationResponse> for User {
-    fn into(self) -> UserAuthenticationResponse {
-        let access_token =
-            generate_access_token(&self.uuid.to_string(), format!("{:?}", self.role).as_str());
-        let refresh_token =
-            generate_refresh_token(&self.uuid.to_string(), format!("{:?}", self.role).as_str());
+impl From<(User, Uuid)> for UserAuthenticationResponse {
+    fn from((user, refresh_jti): (User, Uuid)) -> UserAuthenticationResponse {
+        let tokens = NewTokens::new(user.uuid, user.role, refresh_jti);
         UserAuthenticationResponse {
-            user: self.into(),
-            access_token,
-            refresh_token,
+            user: user.into(),
+            access_token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
         }
     }
 }
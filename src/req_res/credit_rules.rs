@@ -0,0 +1,129 @@
+use crate::models::credit_rules::CreditRuleKind;
+use crate::models::user::User;
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use crate::schema::private;
+use chrono::NaiveDateTime;
+use diesel::Insertable;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct NewCreditRuleReq {
+    pub name: String,
+    pub kind: CreditRuleKind,
+    pub amount: i32,
+    pub day_of_month: Option<i32>,
+    pub run_at: Option<NaiveDateTime>,
+    pub school: Option<String>,
+    pub bunk: Option<String>,
+    pub expires_in_days: Option<i32>,
+    /// Create the rule paused so it can be previewed before it pays out
+    #[serde(default)]
+    pub paused: bool,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = private::credit_rules)]
+pub struct NewCreditRule {
+    pub name: String,
+    pub kind: CreditRuleKind,
+    pub amount: i32,
+    pub day_of_month: Option<i32>,
+    pub run_at: Option<NaiveDateTime>,
+    pub school: Option<String>,
+    pub bunk: Option<String>,
+    pub paused_at: Option<NaiveDateTime>,
+    pub expires_in_days: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CreditRecipient {
+    pub user_uuid: Uuid,
+    pub resident_id: String,
+    pub name: String,
+}
+
+impl From<User> for CreditRecipient {
+    fn from(user: User) -> CreditRecipient {
+        CreditRecipient {
+            user_uuid: user.uuid,
+            resident_id: user.resident_id,
+            name: user.name,
+        }
+    }
+}
+
+/// What the scheduler would pay out for the rule right now.
+#[derive(Debug, Serialize, Clone)]
+pub struct CreditRulePreview {
+    /// Periods with someone left to pay, oldest first. Empty if the rule is not due
+    pub periods: Vec<CreditPeriodPreview>,
+    pub amount: i32,
+    pub total: i64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CreditPeriodPreview {
+    pub period: String,
+    pub recipients: Vec<CreditRecipient>,
+}
+
+impl TryInto<NewCreditRule> for NewCreditRuleReq {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<NewCreditRule, Self::Error> {
+        let mut errors = vec![];
+        let name = self.name.trim().to_string();
+        let school = self
+            .school
+            .map(|school| school.trim().to_string())
+            .filter(|school| !school.is_empty());
+        let bunk = self
+            .bunk
+            .map(|bunk| bunk.trim().to_string())
+            .filter(|bunk| !bunk.is_empty());
+
+        if name.is_empty() {
+            errors.push("Name is required".to_string());
+        }
+        if self.amount <= 0 {
+            errors.push("Amount must be greater than 0".to_string());
+        }
+        if self.expires_in_days.is_some_and(|days| days <= 0) {
+            errors.push("Expiry must be at least 1 day".to_string());
+        }
+
+        // Only keep the schedule field that applies to the kind of rule
+        let mut day_of_month = None;
+        let mut run_at = None;
+        match self.kind {
+            CreditRuleKind::Monthly => match self.day_of_month {
+                Some(day) if (1..=28).contains(&day) => day_of_month = Some(day),
+                _ => errors.push("Monthly rules need a day of month from 1 to 28".to_string()),
+            },
+            CreditRuleKind::Once => match self.run_at {
+                Some(at) => run_at = Some(at),
+                None => errors.push("One-off rules need a run time".to_string()),
+            },
+            CreditRuleKind::Birthday => {}
+        }
+
+        if errors.is_empty() {
+            Ok(NewCreditRule {
+                name,
+                kind: self.kind,
+                amount: self.amount,
+                day_of_month,
+                run_at,
+                school,
+                bunk,
+                paused_at: None,
+                expires_in_days: self.expires_in_days,
+            })
+        } else {
+            Err(AppError::bad_request::<ClientErrorMessages>(
+                DataValidationError { errors }.into(),
+            ))
+        }
+    }
+}
@@ -1,7 +1,13 @@
-use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use crate::models::products::ProductImage;
+use crate::models::stock::StockMovementKind;
+use crate::req_res::{
+    AppError, ClientErrorMessages, DataValidationError, ImportRowError, MAX_PAGE,
+};
 use crate::schema::private;
+use chrono::{NaiveDate, NaiveDateTime};
 use diesel::{AsChangeset, Insertable};
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer, Serialize};
+use uuid::Uuid;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct NewProductReq {
@@ -9,16 +15,27 @@ pub struct NewProductReq {
     pub description: String,
     pub stock: i32,
     pub cost: i32,
+    pub category_id: Option<i32>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub reorder_threshold: Option<i32>,
 }
 
 #[derive(Debug, Insertable, Deserialize)]
 #[diesel(table_name = private::products)]
 pub struct NewProduct {
     pub title: String,
-    pub image_path: String,
+    #[diesel(embed)]
+    pub image: ProductImage,
     pub description: String,
+    /// Recorded as the first restock, products are inserted without stock
+    #[diesel(skip_insertion)]
     pub stock: i32,
     pub cost: i32,
+    pub category_id: Option<i32>,
+    #[diesel(skip_insertion)]
+    pub tags: Vec<String>,
+    pub reorder_threshold: i32,
 }
 
 #[derive(Debug, Deserialize)]
@@ -27,15 +44,163 @@ pub struct UpdateProductReq {
     pub description: Option<String>,
     pub stock: Option<i32>,
     pub cost: Option<i32>,
+    /// `null` removes the product from its category, omitting the field leaves it unchanged
+    #[serde(default, deserialize_with = "nullable")]
+    pub category_id: Option<Option<i32>>,
+    pub tags: Option<Vec<String>>,
+    pub reorder_threshold: Option<i32>,
+}
+
+#[derive(Debug)]
+pub struct UpdateProduct {
+    pub changes: ProductChanges,
+    pub tags: Option<Vec<String>>,
 }
 
 #[derive(Debug, AsChangeset)]
 #[diesel(table_name = private::products)]
-pub struct UpdateProduct {
+pub struct ProductChanges {
     pub title: Option<String>,
     pub description: Option<String>,
-    pub stock: Option<i32>,
     pub cost: Option<i32>,
+    pub category_id: Option<Option<i32>>,
+    pub reorder_threshold: Option<i32>,
+}
+
+impl ProductChanges {
+    /// Diesel rejects an update without any column to set.
+    pub fn is_empty(&self) -> bool {
+        !(self.title.is_some()
+            || self.description.is_some()
+            || self.cost.is_some()
+            || self.category_id.is_some()
+            || self.reorder_threshold.is_some())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StockAdjustmentReq {
+    pub delta: i32,
+    pub kind: StockMovementKind,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StockAdjustment {
+    pub delta: i32,
+    pub kind: StockMovementKind,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MovementQueryParams {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    pub kind: Option<StockMovementKind>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MovementQuery {
+    pub page: i64,
+    pub per_page: i64,
+    pub kind: Option<StockMovementKind>,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
+
+/// One line of the catalogue CSV, used for both import and export.
+/// Existing products are matched by `uuid`, rows without one create a product.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProductCsvRow {
+    pub uuid: Option<Uuid>,
+    pub title: String,
+    pub description: String,
+    pub stock: i32,
+    pub cost: i32,
+    /// Category name
+    pub category: Option<String>,
+    /// Tag names separated by `;`
+    pub tags: Option<String>,
+    pub reorder_threshold: Option<i32>,
+    /// File name inside the uploaded image archive, or the stored full size path on export
+    pub image: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub created: usize,
+    pub updated: usize,
+    pub errors: Vec<ImportRowError>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewCategoryReq {
+    pub name: String,
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = private::categories)]
+pub struct NewCategory {
+    pub name: String,
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateCategoryReq {
+    pub name: Option<String>,
+    /// `null` turns the category into a top level one
+    #[serde(default, deserialize_with = "nullable")]
+    pub parent_id: Option<Option<i32>>,
+}
+
+#[derive(Debug, AsChangeset)]
+#[diesel(table_name = private::categories)]
+pub struct UpdateCategory {
+    pub name: Option<String>,
+    pub parent_id: Option<Option<i32>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TagReq {
+    pub name: String,
+}
+
+#[derive(Debug, Insertable, AsChangeset)]
+#[diesel(table_name = private::tags)]
+pub struct NewTag {
+    pub name: String,
+}
+
+/// Distinguishes an explicit `null` from a missing field.
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Tags are matched case-insensitively, so they are stored trimmed and lowercase.
+fn normalise_tags(tags: Vec<String>, errors: &mut Vec<String>) -> Vec<String> {
+    let mut tags = tags
+        .into_iter()
+        .map(|tag| tag.trim().to_lowercase())
+        .collect::<Vec<String>>();
+    if tags.iter().any(|tag| tag.is_empty()) {
+        errors.push("Tags cannot be empty".to_string());
+    }
+    tags.sort();
+    tags.dedup();
+    tags
 }
 
 impl TryInto<UpdateProduct> for UpdateProductReq {
@@ -44,10 +209,8 @@ impl TryInto<UpdateProduct> for UpdateProductReq {
     fn try_into(self) -> Result<UpdateProduct, Self::Error> {
         let mut errors = vec![];
 
-        if let Some(stock) = self.stock {
-            if stock < 0 {
-                errors.push("Stock cannot be negative".to_string());
-            }
+        if self.stock.is_some() {
+            errors.push("Stock can only be changed with a stock adjustment".to_string());
         }
 
         if let Some(cost) = self.cost {
@@ -56,12 +219,25 @@ impl TryInto<UpdateProduct> for UpdateProductReq {
             }
         }
 
+        if self
+            .reorder_threshold
+            .is_some_and(|threshold| threshold < 0)
+        {
+            errors.push("Reorder threshold cannot be negative".to_string());
+        }
+
+        let tags = self.tags.map(|tags| normalise_tags(tags, &mut errors));
+
         if errors.is_empty() {
             Ok(UpdateProduct {
-                title: self.title,
-                description: self.description,
-                stock: self.stock,
-                cost: self.cost,
+                changes: ProductChanges {
+                    title: self.title,
+                    description: self.description,
+                    cost: self.cost,
+                    category_id: self.category_id,
+                    reorder_threshold: self.reorder_threshold,
+                },
+                tags,
             })
         } else {
             Err(AppError::bad_request::<ClientErrorMessages>(
@@ -71,10 +247,9 @@ impl TryInto<UpdateProduct> for UpdateProductReq {
     }
 }
 
-impl TryInto<NewProduct> for NewProductReq {
-    type Error = AppError;
-
-    fn try_into(self) -> Result<NewProduct, Self::Error> {
+impl NewProductReq {
+    /// Shared by the create endpoint and the CSV import, which reports errors per row.
+    pub fn validate(self) -> Result<NewProduct, Vec<String>> {
         let mut errors = vec![];
 
         if self.stock < 0 {
@@ -84,13 +259,181 @@ impl TryInto<NewProduct> for NewProductReq {
             errors.push("Cost cannot be negative".to_string());
         }
 
+        let reorder_threshold = self.reorder_threshold.unwrap_or(0);
+        if reorder_threshold < 0 {
+            errors.push("Reorder threshold cannot be negative".to_string());
+        }
+
+        let tags = normalise_tags(self.tags, &mut errors);
+
         if errors.is_empty() {
             Ok(NewProduct {
                 title: self.title,
-                image_path: "".to_string(),
+                image: ProductImage::default(),
                 description: self.description,
                 stock: self.stock,
                 cost: self.cost,
+                category_id: self.category_id,
+                tags,
+                reorder_threshold,
+            })
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl TryInto<NewProduct> for NewProductReq {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<NewProduct, Self::Error> {
+        self.validate().map_err(|errors| {
+            AppError::bad_request::<ClientErrorMessages>(DataValidationError { errors }.into())
+        })
+    }
+}
+
+impl TryInto<NewCategory> for NewCategoryReq {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<NewCategory, Self::Error> {
+        let name = self.name.trim().to_string();
+        if name.is_empty() {
+            let errors = vec!["Category name cannot be empty".to_string()];
+            return Err(AppError::bad_request::<ClientErrorMessages>(
+                DataValidationError { errors }.into(),
+            ));
+        }
+
+        Ok(NewCategory {
+            name,
+            parent_id: self.parent_id,
+        })
+    }
+}
+
+impl TryInto<UpdateCategory> for UpdateCategoryReq {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<UpdateCategory, Self::Error> {
+        let mut errors = vec![];
+        let name = self.name.map(|name| name.trim().to_string());
+
+        if name.as_ref().is_some_and(|name| name.is_empty()) {
+            errors.push("Category name cannot be empty".to_string());
+        }
+        if name.is_none() && self.parent_id.is_none() {
+            errors.push("Nothing to update".to_string());
+        }
+
+        if errors.is_empty() {
+            Ok(UpdateCategory {
+                name,
+                parent_id: self.parent_id,
+            })
+        } else {
+            Err(AppError::bad_request::<ClientErrorMessages>(
+                DataValidationError { errors }.into(),
+            ))
+        }
+    }
+}
+
+impl TryInto<NewTag> for TagReq {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<NewTag, Self::Error> {
+        let mut errors = vec![];
+        let mut names = normalise_tags(vec![self.name], &mut errors);
+
+        match names.pop() {
+            Some(name) if errors.is_empty() => Ok(NewTag { name }),
+            _ => Err(AppError::bad_request::<ClientErrorMessages>(
+                DataValidationError { errors }.into(),
+            )),
+        }
+    }
+}
+
+impl TryInto<StockAdjustment> for StockAdjustmentReq {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<StockAdjustment, Self::Error> {
+        let mut errors = vec![];
+        let reason = self
+            .reason
+            .map(|reason| reason.trim().to_string())
+            .filter(|reason| !reason.is_empty());
+
+        if self.delta == 0 {
+            errors.push("Delta cannot be 0".to_string());
+        }
+        match self.kind {
+            StockMovementKind::Restock if self.delta < 0 => {
+                errors.push("A restock must add stock".to_string());
+            }
+            StockMovementKind::WriteOff if self.delta > 0 => {
+                errors.push("A write-off must remove stock".to_string());
+            }
+            StockMovementKind::Redemption | StockMovementKind::Cancellation => {
+                errors.push("Order movements are recorded by the order workflow".to_string());
+            }
+            _ => {}
+        }
+        // Corrections and losses are the movements that need explaining later
+        if matches!(
+            self.kind,
+            StockMovementKind::Adjustment | StockMovementKind::WriteOff
+        ) && reason.is_none()
+        {
+            errors.push("Reason is required".to_string());
+        }
+
+        if errors.is_empty() {
+            Ok(StockAdjustment {
+                delta: self.delta,
+                kind: self.kind,
+                reason,
+            })
+        } else {
+            Err(AppError::bad_request::<ClientErrorMessages>(
+                DataValidationError { errors }.into(),
+            ))
+        }
+    }
+}
+
+impl TryInto<MovementQuery> for MovementQueryParams {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<MovementQuery, Self::Error> {
+        let mut errors = vec![];
+        let page = self.page.unwrap_or(1);
+        let per_page = self.per_page.unwrap_or(20);
+
+        if !(1..=MAX_PAGE).contains(&page) {
+            errors.push(format!("Page must be between 1 and {}", MAX_PAGE));
+        }
+        if !(1..=100).contains(&per_page) {
+            errors.push("Page size must be between 1 and 100".to_string());
+        }
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from > to {
+                errors.push("Start date must not be after end date".to_string());
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(MovementQuery {
+                page,
+                per_page,
+                kind: self.kind,
+                from: self.from.and_then(|d| d.and_hms_opt(0, 0, 0)),
+                // The end date is inclusive, so filter on the start of the following day
+                to: self
+                    .to
+                    .and_then(|d| d.succ_opt())
+                    .and_then(|d| d.and_hms_opt(0, 0, 0)),
             })
         } else {
             Err(AppError::bad_request::<ClientErrorMessages>(
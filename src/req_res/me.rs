@@ -1,8 +1,10 @@
 use crate::models::user::{AccountType, UserAddress};
-use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use crate::models::wallet::Wallet;
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError, MAX_PAGE};
 use crate::schema::private;
+use chrono::{NaiveDate, NaiveDateTime};
 use diesel::AsChangeset;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, AsChangeset)]
 #[diesel(table_name = private::users)]
@@ -17,6 +19,36 @@ pub struct UpdateUser {
     pub school: Option<String>,
 }
 
+#[derive(Debug, Serialize, Clone)]
+pub struct WalletRes {
+    #[serde(flatten)]
+    pub wallet: Wallet,
+    /// Points still to lapse, soonest first
+    pub expiring: Vec<ExpiringPoints>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ExpiringPoints {
+    pub amount: i64,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TransactionQueryParams {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TransactionQuery {
+    pub page: i64,
+    pub per_page: i64,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct PasswordChangeReq {
     pub password: String,
@@ -54,3 +86,42 @@ impl TryInto<PasswordChangeValidated> for PasswordChangeReq {
         }
     }
 }
+
+impl TryInto<TransactionQuery> for TransactionQueryParams {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<TransactionQuery, Self::Error> {
+        let mut errors = vec![];
+        let page = self.page.unwrap_or(1);
+        let per_page = self.per_page.unwrap_or(20);
+
+        if !(1..=MAX_PAGE).contains(&page) {
+            errors.push(format!("Page must be between 1 and {}", MAX_PAGE));
+        }
+        if !(1..=100).contains(&per_page) {
+            errors.push("Page size must be between 1 and 100".to_string());
+        }
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from > to {
+                errors.push("Start date must not be after end date".to_string());
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(TransactionQuery {
+                page,
+                per_page,
+                from: self.from.and_then(|d| d.and_hms_opt(0, 0, 0)),
+                // The end date is inclusive, so filter on the start of the following day
+                to: self
+                    .to
+                    .and_then(|d| d.succ_opt())
+                    .and_then(|d| d.and_hms_opt(0, 0, 0)),
+            })
+        } else {
+            Err(AppError::bad_request::<ClientErrorMessages>(
+                DataValidationError { errors }.into(),
+            ))
+        }
+    }
+}
@@ -1,12 +1,18 @@
+pub mod auctions;
+pub mod audit;
 pub mod auth;
+pub mod credit_rules;
 pub mod inventory;
 pub mod me;
 pub mod orders;
+pub mod policies;
+pub mod product_requests;
 pub mod products;
+pub mod tasks;
 pub mod users;
 
 use axum::extract::multipart::MultipartError;
-use axum::http::StatusCode;
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
 use diesel::result::Error;
@@ -15,6 +21,25 @@ use diesel_async::pooled_connection::PoolError;
 use log::error;
 use serde::{Deserialize, Serialize};
 
+/// Highest page a listing accepts, keeps `(page - 1) * per_page` well within an i64 offset.
+pub const MAX_PAGE: i64 = 100_000;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+}
+
+/// A rejected row of a CSV import.
+#[derive(Debug, Serialize)]
+pub struct ImportRowError {
+    /// Line number in the CSV, the header is line 1
+    pub line: usize,
+    pub errors: Vec<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DataValidationError {
     pub errors: Vec<String>,
@@ -24,6 +49,7 @@ pub struct DataValidationError {
 #[serde(tag = "type")]
 pub enum ClientErrorMessages {
     DataValidationError(DataValidationError),
+    AccountSuspended,
 }
 
 impl From<DataValidationError> for ClientErrorMessages {
@@ -39,6 +65,8 @@ enum ErrorKind {
     UnprocessableEntity,
     BadRequest(Option<ClientErrorMessages>),
     InternalError(String),
+    AccountSuspended,
+    TooManyRequests(u64),
     MethodNotAllowed,
     NoContent,
     ServiceUnavailable,
@@ -78,6 +106,14 @@ impl AppError {
         AppError(ErrorKind::NotFound)
     }
 
+    pub(crate) fn account_suspended() -> Self {
+        AppError(ErrorKind::AccountSuspended)
+    }
+
+    pub(crate) fn too_many_requests(retry_after_secs: u64) -> Self {
+        AppError(ErrorKind::TooManyRequests(retry_after_secs))
+    }
+
     pub(crate) fn method_not_allowed() -> Self {
         AppError(ErrorKind::MethodNotAllowed)
     }
@@ -100,6 +136,16 @@ impl IntoResponse for AppError {
                 .map_or((StatusCode::BAD_REQUEST, ()).into_response(), |errors| {
                     (StatusCode::BAD_REQUEST, Json(errors)).into_response()
                 }),
+            ErrorKind::AccountSuspended => (
+                StatusCode::FORBIDDEN,
+                Json(ClientErrorMessages::AccountSuspended),
+            )
+                .into_response(),
+            ErrorKind::TooManyRequests(retry_after) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+            )
+                .into_response(),
             ErrorKind::NotFound => (StatusCode::NOT_FOUND, ()).into_response(),
             ErrorKind::NoContent => (StatusCode::NO_CONTENT, ()).into_response(),
             ErrorKind::MethodNotAllowed => (StatusCode::METHOD_NOT_ALLOWED, ()).into_response(),
@@ -128,6 +174,13 @@ impl From<RunError> for AppError {
     }
 }
 
+impl From<axum_casbin::casbin::Error> for AppError {
+    fn from(value: axum_casbin::casbin::Error) -> Self {
+        error!("Casbin: {}", value);
+        Self::internal_error("Policy error".to_string())
+    }
+}
+
 impl From<MultipartError> for AppError {
     fn from(value: MultipartError) -> Self {
         error!("Error processing multipart request: {}", value);
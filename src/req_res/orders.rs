@@ -1 +1,90 @@
+use crate::models::orders::{Order, OrderStatus};
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use uuid::Uuid;
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct OrderItemReq {
+    pub product_uuid: Uuid,
+    pub quantity: i32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct NewOrderReq {
+    pub items: Vec<OrderItemReq>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewOrderValidated {
+    pub items: Vec<OrderItemReq>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrderQueryParams {
+    pub status: Option<OrderStatus>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct OrderItemRes {
+    pub product_uuid: Uuid,
+    pub title: String,
+    pub quantity: i32,
+    pub unit_cost: i32,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct OrderRes {
+    pub uuid: Uuid,
+    pub user_uuid: Uuid,
+    pub status: OrderStatus,
+    pub total_cost: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub items: Vec<OrderItemRes>,
+}
+
+impl From<(Order, Vec<OrderItemRes>)> for OrderRes {
+    fn from((order, items): (Order, Vec<OrderItemRes>)) -> OrderRes {
+        OrderRes {
+            uuid: order.uuid,
+            user_uuid: order.user_uuid,
+            status: order.status,
+            total_cost: order.total_cost,
+            created_at: order.created_at,
+            updated_at: order.updated_at,
+            items,
+        }
+    }
+}
+
+impl TryInto<NewOrderValidated> for NewOrderReq {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<NewOrderValidated, Self::Error> {
+        let mut errors = vec![];
+
+        if self.items.is_empty() {
+            errors.push("Order must contain at least one item".to_string());
+        }
+        if self.items.iter().any(|item| item.quantity <= 0) {
+            errors.push("Quantity must be at least 1".to_string());
+        }
+        let mut seen = HashSet::new();
+        if !self.items.iter().all(|item| seen.insert(item.product_uuid)) {
+            errors.push("Each product can only appear once per order".to_string());
+        }
+
+        if errors.is_empty() {
+            let mut items = self.items;
+            // Lock product rows in a consistent order so concurrent orders cannot deadlock
+            items.sort_by_key(|item| item.product_uuid);
+            Ok(NewOrderValidated { items })
+        } else {
+            Err(AppError::bad_request::<ClientErrorMessages>(
+                DataValidationError { errors }.into(),
+            ))
+        }
+    }
+}
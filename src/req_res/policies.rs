@@ -0,0 +1,63 @@
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PolicyRuleReq {
+    pub ptype: String,
+    pub rule: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PolicyRule {
+    pub ptype: String,
+    pub rule: Vec<String>,
+}
+
+impl PolicyRule {
+    pub fn is_grouping(&self) -> bool {
+        self.ptype.starts_with('g')
+    }
+}
+
+impl TryInto<PolicyRule> for PolicyRuleReq {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<PolicyRule, Self::Error> {
+        let mut errors = vec![];
+        let rule = self
+            .rule
+            .into_iter()
+            .map(|value| value.trim().to_string())
+            .collect::<Vec<String>>();
+
+        let expected_len = match self.ptype.as_str() {
+            "p" => Some(3),
+            "g" | "g2" | "g3" => Some(2),
+            _ => None,
+        };
+        match expected_len {
+            Some(len) if rule.len() != len => {
+                errors.push(format!(
+                    "Rule of type {} must have {} values",
+                    self.ptype, len
+                ));
+            }
+            Some(_) => {}
+            None => errors.push("Rule type must be one of p, g, g2 or g3".to_string()),
+        }
+        if rule.iter().any(|value| value.is_empty()) {
+            errors.push("Rule values must not be empty".to_string());
+        }
+
+        if errors.is_empty() {
+            Ok(PolicyRule {
+                ptype: self.ptype,
+                rule,
+            })
+        } else {
+            Err(AppError::bad_request::<ClientErrorMessages>(
+                DataValidationError { errors }.into(),
+            ))
+        }
+    }
+}
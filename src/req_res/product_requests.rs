@@ -0,0 +1,144 @@
+use crate::models::product_requests::{ProductRequest, ProductRequestStatus};
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use crate::schema::private;
+use chrono::NaiveDateTime;
+use diesel::Insertable;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct NewProductRequestReq {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub quantity: i32,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = private::product_requests)]
+pub struct NewProductRequest {
+    pub title: String,
+    pub description: String,
+    pub quantity: i32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RejectProductRequestReq {
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RejectProductRequest {
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProductRequestQueryParams {
+    pub status: Option<ProductRequestStatus>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ProductRequestRes {
+    #[serde(flatten)]
+    pub request: ProductRequest,
+    pub votes: i64,
+    /// Whether the caller has upvoted the request
+    pub voted: bool,
+}
+
+/// What residents see of a request. Who asked for it and who reviewed it stay with staff.
+#[derive(Debug, Serialize, Clone)]
+pub struct ResidentProductRequestRes {
+    pub uuid: Uuid,
+    pub title: String,
+    pub description: String,
+    pub quantity: i32,
+    pub status: ProductRequestStatus,
+    pub review_reason: Option<String>,
+    pub product_uuid: Option<Uuid>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub votes: i64,
+    pub voted: bool,
+    /// Whether the caller made the request
+    pub mine: bool,
+}
+
+impl ResidentProductRequestRes {
+    pub fn new(res: ProductRequestRes, user: Uuid) -> ResidentProductRequestRes {
+        let request = res.request;
+        ResidentProductRequestRes {
+            mine: request.user_uuid == user,
+            uuid: request.uuid,
+            title: request.title,
+            description: request.description,
+            quantity: request.quantity,
+            status: request.status,
+            review_reason: request.review_reason,
+            product_uuid: request.product_uuid,
+            created_at: request.created_at,
+            updated_at: request.updated_at,
+            votes: res.votes,
+            voted: res.voted,
+        }
+    }
+}
+
+impl TryInto<NewProductRequest> for NewProductRequestReq {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<NewProductRequest, Self::Error> {
+        let mut errors = vec![];
+        let title = self.title.trim().to_string();
+        let description = self.description.trim().to_string();
+
+        if title.is_empty() {
+            errors.push("Item name is required".to_string());
+        }
+        if title.chars().count() > 100 {
+            errors.push("Item name cannot exceed 100 characters".to_string());
+        }
+        if description.chars().count() > 1000 {
+            errors.push("Description cannot exceed 1000 characters".to_string());
+        }
+        if !(1..=100).contains(&self.quantity) {
+            errors.push("Quantity must be between 1 and 100".to_string());
+        }
+
+        if errors.is_empty() {
+            Ok(NewProductRequest {
+                title,
+                description,
+                quantity: self.quantity,
+            })
+        } else {
+            Err(AppError::bad_request::<ClientErrorMessages>(
+                DataValidationError { errors }.into(),
+            ))
+        }
+    }
+}
+
+impl TryInto<RejectProductRequest> for RejectProductRequestReq {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<RejectProductRequest, Self::Error> {
+        let reason = self.reason.trim().to_string();
+        let mut errors = vec![];
+
+        if reason.is_empty() {
+            errors.push("Reason is required".to_string());
+        }
+        if reason.chars().count() > 500 {
+            errors.push("Reason cannot exceed 500 characters".to_string());
+        }
+
+        if errors.is_empty() {
+            Ok(RejectProductRequest { reason })
+        } else {
+            Err(AppError::bad_request::<ClientErrorMessages>(
+                DataValidationError { errors }.into(),
+            ))
+        }
+    }
+}
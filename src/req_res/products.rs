@@ -1,6 +1,116 @@
+use crate::models::products::ImageSize;
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError, MAX_PAGE};
 use serde::Deserialize;
 
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProductSort {
+    Title,
+    Cost,
+    Stock,
+    Relevance,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UploadQuery {
+    pub size: Option<ImageSize>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SearchParams {
     pub q: Option<String>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    pub sort: Option<ProductSort>,
+    pub order: Option<SortOrder>,
+    pub min_cost: Option<i32>,
+    pub max_cost: Option<i32>,
+    pub in_stock: Option<bool>,
+    pub category: Option<i32>,
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProductQuery {
+    pub q: Option<String>,
+    pub page: i64,
+    pub per_page: i64,
+    pub sort: ProductSort,
+    pub order: SortOrder,
+    pub min_cost: Option<i32>,
+    pub max_cost: Option<i32>,
+    pub in_stock: bool,
+    /// Includes products in any subcategory
+    pub category: Option<i32>,
+    pub tag: Option<String>,
+}
+
+impl TryInto<ProductQuery> for SearchParams {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<ProductQuery, Self::Error> {
+        let mut errors = vec![];
+        let q = self.q.filter(|q| !q.trim().is_empty());
+        let tag = self
+            .tag
+            .map(|tag| tag.trim().to_lowercase())
+            .filter(|tag| !tag.is_empty());
+        let page = self.page.unwrap_or(1);
+        let per_page = self.per_page.unwrap_or(20);
+        // Search results read best by relevance, everything else alphabetically
+        let sort = self.sort.unwrap_or(if q.is_some() {
+            ProductSort::Relevance
+        } else {
+            ProductSort::Title
+        });
+        let order = self.order.unwrap_or(match sort {
+            ProductSort::Relevance => SortOrder::Desc,
+            _ => SortOrder::Asc,
+        });
+
+        if !(1..=MAX_PAGE).contains(&page) {
+            errors.push(format!("Page must be between 1 and {}", MAX_PAGE));
+        }
+        if !(1..=100).contains(&per_page) {
+            errors.push("Page size must be between 1 and 100".to_string());
+        }
+        if sort == ProductSort::Relevance && q.is_none() {
+            errors.push("Sorting by relevance requires a search term".to_string());
+        }
+        if self.min_cost.is_some_and(|cost| cost < 0) || self.max_cost.is_some_and(|cost| cost < 0)
+        {
+            errors.push("Cost filters must not be negative".to_string());
+        }
+        if let (Some(min_cost), Some(max_cost)) = (self.min_cost, self.max_cost) {
+            if min_cost > max_cost {
+                errors.push("Minimum cost must not be above maximum cost".to_string());
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(ProductQuery {
+                q,
+                page,
+                per_page,
+                sort,
+                order,
+                min_cost: self.min_cost,
+                max_cost: self.max_cost,
+                in_stock: self.in_stock.unwrap_or(false),
+                category: self.category,
+                tag,
+            })
+        } else {
+            Err(AppError::bad_request::<ClientErrorMessages>(
+                DataValidationError { errors }.into(),
+            ))
+        }
+    }
 }
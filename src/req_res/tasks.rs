@@ -0,0 +1,128 @@
+use crate::models::tasks::{SubmissionStatus, Task, TaskSubmission};
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use crate::schema::private;
+use chrono::{NaiveDateTime, Utc};
+use diesel::Insertable;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct NewTaskReq {
+    pub title: String,
+    pub description: String,
+    pub reward: i32,
+    pub capacity: i32,
+    pub deadline: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = private::tasks)]
+pub struct NewTask {
+    pub title: String,
+    pub description: String,
+    pub reward: i32,
+    pub capacity: i32,
+    pub deadline: NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RejectSubmissionReq {
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RejectSubmission {
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubmissionQueryParams {
+    pub status: Option<SubmissionStatus>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TaskRes {
+    #[serde(flatten)]
+    pub task: Task,
+    /// Claims that have not been rejected
+    pub taken: i64,
+    /// The caller's own claim, only filled in for residents
+    pub my_status: Option<SubmissionStatus>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SubmissionRes {
+    #[serde(flatten)]
+    pub submission: TaskSubmission,
+    pub task_title: String,
+    pub reward: i32,
+}
+
+impl From<(TaskSubmission, Task)> for SubmissionRes {
+    fn from((submission, task): (TaskSubmission, Task)) -> SubmissionRes {
+        SubmissionRes {
+            submission,
+            task_title: task.title,
+            reward: task.reward,
+        }
+    }
+}
+
+impl TryInto<NewTask> for NewTaskReq {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<NewTask, Self::Error> {
+        let mut errors = vec![];
+        let title = self.title.trim().to_string();
+
+        if title.is_empty() {
+            errors.push("Title is required".to_string());
+        }
+        if self.reward <= 0 {
+            errors.push("Reward must be greater than 0".to_string());
+        }
+        if self.capacity <= 0 {
+            errors.push("Capacity must be at least 1".to_string());
+        }
+        if self.deadline <= Utc::now().naive_utc() {
+            errors.push("Deadline must be in the future".to_string());
+        }
+
+        if errors.is_empty() {
+            Ok(NewTask {
+                title,
+                description: self.description,
+                reward: self.reward,
+                capacity: self.capacity,
+                deadline: self.deadline,
+            })
+        } else {
+            Err(AppError::bad_request::<ClientErrorMessages>(
+                DataValidationError { errors }.into(),
+            ))
+        }
+    }
+}
+
+impl TryInto<RejectSubmission> for RejectSubmissionReq {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<RejectSubmission, Self::Error> {
+        let reason = self.reason.trim().to_string();
+        let mut errors = vec![];
+
+        if reason.is_empty() {
+            errors.push("Reason is required".to_string());
+        }
+        if reason.chars().count() > 500 {
+            errors.push("Reason cannot exceed 500 characters".to_string());
+        }
+
+        if errors.is_empty() {
+            Ok(RejectSubmission { reason })
+        } else {
+            Err(AppError::bad_request::<ClientErrorMessages>(
+                DataValidationError { errors }.into(),
+            ))
+        }
+    }
+}
@@ -1,9 +1,9 @@
-use crate::helper::hash_password;
 use crate::models::user::{AccountType, User, UserAddress};
 use crate::models::wallet::Wallet;
 use crate::req_res::auth::NewUser;
 use crate::req_res::me::UpdateUser;
-use crate::req_res::{AppError, ClientErrorMessages, DataValidationError};
+use crate::req_res::{AppError, ClientErrorMessages, DataValidationError, ImportRowError};
+use chrono::{NaiveDateTime, Utc};
 use num_traits::cast::ToPrimitive;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -32,6 +32,87 @@ pub struct AdminUpdateUserReq {
     pub school: Option<String>,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct WalletAdjustmentReq {
+    pub amount: i32,
+    pub description: String,
+    /// When credited points lapse, ignored for debits
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WalletAdjustment {
+    pub amount: i32,
+    pub description: String,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+/// One line of a resident onboarding CSV. The address columns are either all set or all empty.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ResidentCsvRow {
+    pub resident_id: String,
+    pub name: String,
+    pub phone: String,
+    pub email: String,
+    pub dob: Option<String>,
+    pub school: Option<String>,
+    pub bunk: Option<String>,
+    pub floor: Option<u8>,
+    pub unit: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportUsersQuery {
+    pub dry_run: Option<bool>,
+    /// Send the generated passwords to residents instead of returning them
+    pub deliver: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct UserImportReport {
+    pub dry_run: bool,
+    pub delivered: bool,
+    pub created: usize,
+    /// Residents whose welcome message could not be queued
+    pub undelivered: usize,
+    pub errors: Vec<ImportRowError>,
+    /// Filled in when the passwords were not delivered, or for each undelivered resident
+    pub credentials: Vec<GeneratedCredential>,
+}
+
+/// Whether a generated password was queued for the user. If queueing failed the
+/// password is returned instead, so staff can hand it over themselves.
+#[derive(Debug, Serialize)]
+pub struct CredentialDelivery {
+    pub delivered: bool,
+    pub password: Option<String>,
+}
+
+impl CredentialDelivery {
+    pub fn new(delivered: bool, password: String) -> CredentialDelivery {
+        CredentialDelivery {
+            delivered,
+            password: (!delivered).then_some(password),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NewUserRes {
+    #[serde(flatten)]
+    pub user: DetailedUser,
+    #[serde(flatten)]
+    pub delivery: CredentialDelivery,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GeneratedCredential {
+    pub uuid: Uuid,
+    pub resident_id: String,
+    pub name: String,
+    pub password: String,
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub struct DetailedUser {
     pub uuid: Uuid,
@@ -94,11 +175,39 @@ impl From<(User, Option<Wallet>)> for DetailedUserFull {
     }
 }
 
-impl TryInto<NewUser> for AdminNewUserReq {
-    type Error = AppError;
+impl TryFrom<ResidentCsvRow> for AdminNewUserReq {
+    type Error = String;
 
-    fn try_into(self) -> Result<NewUser, Self::Error> {
+    fn try_from(row: ResidentCsvRow) -> Result<Self, Self::Error> {
+        let address = match (row.bunk, row.floor, row.unit) {
+            (Some(bunk), Some(floor), Some(unit)) => Some(UserAddress { bunk, floor, unit }),
+            (None, None, None) => None,
+            _ => return Err("Address needs a bunk, floor and unit".to_string()),
+        };
+        Ok(AdminNewUserReq {
+            resident_id: row.resident_id,
+            email: row.email,
+            name: row.name,
+            phone: row.phone,
+            role: AccountType::User,
+            address,
+            dob: row.dob,
+            school: row.school,
+        })
+    }
+}
+
+impl AdminNewUserReq {
+    /// Shared by the create endpoint and the CSV import, which reports errors per row.
+    /// The password is left empty for the caller to fill in.
+    pub fn validate(self) -> Result<NewUser, Vec<String>> {
         let mut errors = vec![];
+        if self.resident_id.trim().is_empty() {
+            errors.push("Resident ID is required".to_string());
+        }
+        if self.name.trim().is_empty() {
+            errors.push("Name is required".to_string());
+        }
         if self.phone.len() != 8 {
             errors.push("Invalid Singapore phone number".to_string());
         }
@@ -107,7 +216,7 @@ impl TryInto<NewUser> for AdminNewUserReq {
                 resident_id: self.resident_id,
                 email: self.email,
                 name: self.name,
-                password: hash_password("placeholder")?,
+                password: String::new(),
                 phone: self.phone,
                 role: self.role,
                 active: true,
@@ -117,13 +226,21 @@ impl TryInto<NewUser> for AdminNewUserReq {
                 force_pw_change: true,
             })
         } else {
-            Err(AppError::bad_request::<ClientErrorMessages>(
-                DataValidationError { errors }.into(),
-            ))
+            Err(errors)
         }
     }
 }
 
+impl TryInto<NewUser> for AdminNewUserReq {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<NewUser, Self::Error> {
+        self.validate().map_err(|errors| {
+            AppError::bad_request::<ClientErrorMessages>(DataValidationError { errors }.into())
+        })
+    }
+}
+
 impl TryInto<UpdateUser> for AdminUpdateUserReq {
     type Error = AppError;
 
@@ -154,3 +271,40 @@ impl TryInto<UpdateUser> for AdminUpdateUserReq {
         }
     }
 }
+
+impl TryInto<WalletAdjustment> for WalletAdjustmentReq {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<WalletAdjustment, Self::Error> {
+        let mut errors = vec![];
+        let description = self.description.trim().to_string();
+
+        if self.amount <= 0 {
+            errors.push("Amount must be greater than 0".to_string());
+        }
+        if description.is_empty() {
+            errors.push("Description is required".to_string());
+        }
+        if description.chars().count() > 255 {
+            errors.push("Description cannot exceed 255 characters".to_string());
+        }
+        if self
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now().naive_utc())
+        {
+            errors.push("Expiry must be in the future".to_string());
+        }
+
+        if errors.is_empty() {
+            Ok(WalletAdjustment {
+                amount: self.amount,
+                description,
+                expires_at: self.expires_at,
+            })
+        } else {
+            Err(AppError::bad_request::<ClientErrorMessages>(
+                DataValidationError { errors }.into(),
+            ))
+        }
+    }
+}
@@ -6,11 +6,241 @@ pub mod private {
         #[diesel(postgres_type(name = "account_type", schema = "private"))]
         pub struct AccountType;
 
+        #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+        #[diesel(postgres_type(name = "auction_status", schema = "private"))]
+        pub struct AuctionStatus;
+
+        #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+        #[diesel(postgres_type(name = "credit_rule_kind", schema = "private"))]
+        pub struct CreditRuleKind;
+
+        #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+        #[diesel(postgres_type(name = "order_status", schema = "private"))]
+        pub struct OrderStatus;
+
+        #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+        #[diesel(postgres_type(name = "product_request_status", schema = "private"))]
+        pub struct ProductRequestStatus;
+
+        #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+        #[diesel(postgres_type(name = "stock_movement_kind", schema = "private"))]
+        pub struct StockMovementKind;
+
+        #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+        #[diesel(postgres_type(name = "submission_status", schema = "private"))]
+        pub struct SubmissionStatus;
+
         #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
         #[diesel(postgres_type(name = "transaction_type", schema = "private"))]
         pub struct TransactionType;
     }
 
+    diesel::table! {
+        use diesel::sql_types::*;
+        use diesel_full_text_search::Tsvector;
+
+        private.auction_bids (id) {
+            id -> Int8,
+            auction_uuid -> Uuid,
+            user_uuid -> Uuid,
+            amount -> Int4,
+            created_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use diesel_full_text_search::Tsvector;
+        use super::sql_types::AuctionStatus;
+
+        private.auctions (uuid) {
+            uuid -> Uuid,
+            title -> Text,
+            description -> Text,
+            reserve_price -> Int4,
+            starts_at -> Timestamp,
+            ends_at -> Timestamp,
+            status -> AuctionStatus,
+            high_bid -> Nullable<Int4>,
+            high_bidder -> Nullable<Uuid>,
+            created_by -> Uuid,
+            settled_at -> Nullable<Timestamp>,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use diesel_full_text_search::Tsvector;
+
+        private.audit_log (id) {
+            id -> Int8,
+            actor_uuid -> Uuid,
+            action -> Text,
+            target_type -> Text,
+            target_id -> Text,
+            before -> Nullable<Jsonb>,
+            after -> Nullable<Jsonb>,
+            created_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use diesel_full_text_search::Tsvector;
+
+        private.categories (id) {
+            id -> Int4,
+            name -> Text,
+            parent_id -> Nullable<Int4>,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use diesel_full_text_search::Tsvector;
+
+        private.casbin_rules (id) {
+            id -> Int4,
+            ptype -> Text,
+            v0 -> Text,
+            v1 -> Text,
+            v2 -> Text,
+            v3 -> Text,
+            v4 -> Text,
+            v5 -> Text,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use diesel_full_text_search::Tsvector;
+
+        private.credit_lots (id) {
+            id -> Int8,
+            wallet_id -> Int4,
+            amount -> Int4,
+            remaining -> Int4,
+            expires_at -> Nullable<Timestamp>,
+            created_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use diesel_full_text_search::Tsvector;
+
+        private.credit_rule_runs (rule_uuid, user_uuid, period) {
+            rule_uuid -> Uuid,
+            user_uuid -> Uuid,
+            period -> Text,
+            credited_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use diesel_full_text_search::Tsvector;
+        use super::sql_types::CreditRuleKind;
+
+        private.credit_rules (uuid) {
+            uuid -> Uuid,
+            name -> Text,
+            kind -> CreditRuleKind,
+            amount -> Int4,
+            day_of_month -> Nullable<Int4>,
+            run_at -> Nullable<Timestamp>,
+            school -> Nullable<Text>,
+            bunk -> Nullable<Text>,
+            paused_at -> Nullable<Timestamp>,
+            created_by -> Uuid,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
+            expires_in_days -> Nullable<Int4>,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use diesel_full_text_search::Tsvector;
+
+        private.order_credit_lots (order_uuid, lot_id) {
+            order_uuid -> Uuid,
+            lot_id -> Int8,
+            amount -> Int4,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use diesel_full_text_search::Tsvector;
+
+        private.order_items (id) {
+            id -> Int4,
+            order_uuid -> Uuid,
+            product_uuid -> Uuid,
+            quantity -> Int4,
+            unit_cost -> Int4,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use diesel_full_text_search::Tsvector;
+        use super::sql_types::OrderStatus;
+
+        private.orders (uuid) {
+            uuid -> Uuid,
+            user_uuid -> Uuid,
+            status -> OrderStatus,
+            total_cost -> Int4,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use diesel_full_text_search::Tsvector;
+
+        private.product_request_votes (request_uuid, user_uuid) {
+            request_uuid -> Uuid,
+            user_uuid -> Uuid,
+            created_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use diesel_full_text_search::Tsvector;
+        use super::sql_types::ProductRequestStatus;
+
+        private.product_requests (uuid) {
+            uuid -> Uuid,
+            user_uuid -> Uuid,
+            title -> Text,
+            description -> Text,
+            quantity -> Int4,
+            status -> ProductRequestStatus,
+            review_reason -> Nullable<Text>,
+            reviewed_by -> Nullable<Uuid>,
+            product_uuid -> Nullable<Uuid>,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use diesel_full_text_search::Tsvector;
+
+        private.product_tags (product_uuid, tag_id) {
+            product_uuid -> Uuid,
+            tag_id -> Int4,
+        }
+    }
+
     diesel::table! {
         use diesel::sql_types::*;
         use diesel_full_text_search::Tsvector;
@@ -22,7 +252,79 @@ pub mod private {
             description -> Text,
             stock -> Int4,
             cost -> Int4,
+            category_id -> Nullable<Int4>,
+            search_terms -> Text,
             search_vector -> Tsvector,
+            archived_at -> Nullable<Timestamp>,
+            reorder_threshold -> Int4,
+            low_stock_alerted_at -> Nullable<Timestamp>,
+            card_path -> Text,
+            thumbnail_path -> Text,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use diesel_full_text_search::Tsvector;
+        use super::sql_types::StockMovementKind;
+
+        private.stock_movements (id) {
+            id -> Int8,
+            product_uuid -> Uuid,
+            actor_uuid -> Nullable<Uuid>,
+            kind -> StockMovementKind,
+            delta -> Int4,
+            reason -> Nullable<Text>,
+            order_uuid -> Nullable<Uuid>,
+            created_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use diesel_full_text_search::Tsvector;
+
+        private.tags (id) {
+            id -> Int4,
+            name -> Text,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use diesel_full_text_search::Tsvector;
+        use super::sql_types::SubmissionStatus;
+
+        private.task_submissions (uuid) {
+            uuid -> Uuid,
+            task_uuid -> Uuid,
+            user_uuid -> Uuid,
+            status -> SubmissionStatus,
+            note -> Nullable<Text>,
+            photo_path -> Nullable<Text>,
+            review_reason -> Nullable<Text>,
+            reviewed_by -> Nullable<Uuid>,
+            claimed_at -> Timestamp,
+            submitted_at -> Nullable<Timestamp>,
+            reviewed_at -> Nullable<Timestamp>,
+        }
+    }
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use diesel_full_text_search::Tsvector;
+
+        private.tasks (uuid) {
+            uuid -> Uuid,
+            title -> Text,
+            description -> Text,
+            reward -> Int4,
+            capacity -> Int4,
+            deadline -> Timestamp,
+            created_by -> Uuid,
+            closed_at -> Nullable<Timestamp>,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
         }
     }
 
@@ -72,11 +374,52 @@ pub mod private {
             user_uuid -> Uuid,
             balance -> Int4,
             updated_at -> Timestamp,
+            held -> Int4,
         }
     }
 
+    diesel::joinable!(auction_bids -> auctions (auction_uuid));
+    diesel::joinable!(credit_lots -> wallets (wallet_id));
+    diesel::joinable!(credit_rule_runs -> credit_rules (rule_uuid));
+    diesel::joinable!(order_credit_lots -> credit_lots (lot_id));
+    diesel::joinable!(order_credit_lots -> orders (order_uuid));
+    diesel::joinable!(order_items -> orders (order_uuid));
+    diesel::joinable!(order_items -> products (product_uuid));
+    diesel::joinable!(orders -> users (user_uuid));
+    diesel::joinable!(product_request_votes -> product_requests (request_uuid));
+    diesel::joinable!(product_requests -> products (product_uuid));
+    diesel::joinable!(product_tags -> products (product_uuid));
+    diesel::joinable!(product_tags -> tags (tag_id));
+    diesel::joinable!(products -> categories (category_id));
+    diesel::joinable!(stock_movements -> orders (order_uuid));
+    diesel::joinable!(stock_movements -> products (product_uuid));
+    diesel::joinable!(task_submissions -> tasks (task_uuid));
+    diesel::joinable!(tasks -> users (created_by));
     diesel::joinable!(transactions -> wallets (wallet_id));
     diesel::joinable!(wallets -> users (user_uuid));
 
-    diesel::allow_tables_to_appear_in_same_query!(products, transactions, users, wallets,);
+    diesel::allow_tables_to_appear_in_same_query!(
+        auction_bids,
+        auctions,
+        audit_log,
+        casbin_rules,
+        categories,
+        credit_lots,
+        credit_rule_runs,
+        credit_rules,
+        order_credit_lots,
+        order_items,
+        orders,
+        product_request_votes,
+        product_requests,
+        product_tags,
+        products,
+        stock_movements,
+        tags,
+        task_submissions,
+        tasks,
+        transactions,
+        users,
+        wallets,
+    );
 }
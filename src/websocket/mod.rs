@@ -1,6 +1,215 @@
-use serde::Deserialize;
+use crate::helper::validate_token;
+use crate::models::auctions::AuctionStatus;
+use crate::models::orders::OrderStatus;
+use crate::models::products::LowStockProduct;
+use crate::models::user::AccountType;
+use crate::paseto::{AuthTokenClaims, TokenPurpose};
+use chrono::{DateTime, Utc};
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use socketioxide::extract::{Data, Extension, SocketRef};
+use socketioxide::handler::ConnectHandler;
+use socketioxide::SocketIo;
+use std::fmt;
+use uuid::Uuid;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct WSAuthToken {
     pub token: String,
 }
+
+#[derive(Debug)]
+pub struct WSAuthError;
+
+impl fmt::Display for WSAuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unauthorized")
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct StockChanged {
+    pub product_uuid: Uuid,
+    pub stock: i32,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct BalanceChanged {
+    pub user_uuid: Uuid,
+    pub balance: i32,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct OrderStatusChanged {
+    pub order_uuid: Uuid,
+    pub user_uuid: Uuid,
+    pub status: OrderStatus,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct BidPlaced {
+    pub auction_uuid: Uuid,
+    pub high_bid: i32,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct AuctionClosed {
+    pub auction_uuid: Uuid,
+    pub status: AuctionStatus,
+    pub high_bid: Option<i32>,
+}
+
+pub fn register_handlers(io: &SocketIo) {
+    io.ns("/", on_connect.with(authenticate));
+}
+
+/// When the access token a socket connected with stops being valid.
+#[derive(Debug, Clone, Copy)]
+struct TokenExpiry(DateTime<Utc>);
+
+fn authenticate(socket: SocketRef, Data(auth): Data<WSAuthToken>) -> Result<(), WSAuthError> {
+    let (_, claims) = validate_token(&auth.token, TokenPurpose::Access).ok_or(WSAuthError)?;
+    let expires_at = claims
+        .get_claim("exp")
+        .and_then(|exp| exp.as_str())
+        .and_then(|exp| DateTime::parse_from_rfc3339(exp).ok())
+        .ok_or(WSAuthError)?;
+    let claims = AuthTokenClaims::try_from(&claims).map_err(|err| {
+        error!("Error parsing claims {}", err);
+        WSAuthError
+    })?;
+    socket.extensions.insert(claims);
+    socket
+        .extensions
+        .insert(TokenExpiry(expires_at.with_timezone(&Utc)));
+    Ok(())
+}
+
+fn on_connect(
+    socket: SocketRef,
+    Extension(claims): Extension<AuthTokenClaims>,
+    Extension(expiry): Extension<TokenExpiry>,
+) {
+    if let Err(err) = socket.join([user_room(claims.user_uid), role_room(claims.role)]) {
+        warn!("Unable to join rooms for socket {}: {:?}", socket.id, err);
+    }
+
+    // Rooms are only checked on connect, so the socket must not outlive its token
+    let until_expiry = (expiry.0 - Utc::now()).to_std().unwrap_or_default();
+    tokio::spawn(async move {
+        tokio::time::sleep(until_expiry).await;
+        if socket.connected() {
+            if let Err(err) = socket.clone().disconnect() {
+                warn!(
+                    "Unable to disconnect expired socket {}: {:?}",
+                    socket.id, err
+                );
+            }
+        }
+    });
+}
+
+/// Drops every socket of a user, such as when the account is suspended.
+pub fn disconnect_user(io: &SocketIo, user_uuid: Uuid) {
+    if let Err(err) = io.to(user_room(user_uuid)).disconnect() {
+        warn!("Unable to disconnect sockets of {}: {:?}", user_uuid, err);
+    }
+}
+
+fn user_room(user_uuid: Uuid) -> String {
+    format!("user:{}", user_uuid)
+}
+
+fn role_room(role: AccountType) -> String {
+    format!("role:{:?}", role)
+}
+
+/// Stock levels are shown on every kiosk, so the change goes to all sockets.
+pub fn emit_stock_changed(io: &SocketIo, product_uuid: Uuid, stock: i32) {
+    let event = StockChanged {
+        product_uuid,
+        stock,
+    };
+    if let Err(err) = io.emit("product.stock_changed", &event) {
+        warn!("Unable to emit product.stock_changed: {:?}", err);
+    }
+}
+
+pub fn emit_balance_changed(io: &SocketIo, user_uuid: Uuid, balance: i32) {
+    let event = BalanceChanged { user_uuid, balance };
+    let rooms = [
+        user_room(user_uuid),
+        role_room(AccountType::Admin),
+        role_room(AccountType::Auditor),
+    ];
+    if let Err(err) = io.to(rooms).emit("wallet.balance_changed", &event) {
+        warn!("Unable to emit wallet.balance_changed: {:?}", err);
+    }
+}
+
+pub fn emit_order_status_changed(
+    io: &SocketIo,
+    order_uuid: Uuid,
+    user_uuid: Uuid,
+    status: OrderStatus,
+) {
+    let event = OrderStatusChanged {
+        order_uuid,
+        user_uuid,
+        status,
+    };
+    // Volunteers staff the shop counter, so they follow the order workflow too
+    let rooms = [
+        user_room(user_uuid),
+        role_room(AccountType::Admin),
+        role_room(AccountType::Volunteer),
+        role_room(AccountType::Auditor),
+    ];
+    if let Err(err) = io.to(rooms).emit("order.status_changed", &event) {
+        warn!("Unable to emit order.status_changed: {:?}", err);
+    }
+}
+
+/// Restocking is handled by the people running the shop.
+pub fn emit_low_stock(io: &SocketIo, products: &[LowStockProduct]) {
+    let rooms = [
+        role_room(AccountType::Admin),
+        role_room(AccountType::Volunteer),
+    ];
+    if let Err(err) = io.to(rooms).emit("product.low_stock", &products) {
+        warn!("Unable to emit product.low_stock: {:?}", err);
+    }
+}
+
+/// Anyone can follow an auction, so bids go to all sockets. Only the amount is
+/// shared, and the bidder who lost the lead is told separately.
+pub fn emit_bid_placed(io: &SocketIo, auction_uuid: Uuid, high_bid: i32, outbid: Option<Uuid>) {
+    let event = BidPlaced {
+        auction_uuid,
+        high_bid,
+    };
+    if let Err(err) = io.emit("auction.bid_placed", &event) {
+        warn!("Unable to emit auction.bid_placed: {:?}", err);
+    }
+    if let Some(user_uuid) = outbid {
+        if let Err(err) = io.to(user_room(user_uuid)).emit("auction.outbid", &event) {
+            warn!("Unable to emit auction.outbid: {:?}", err);
+        }
+    }
+}
+
+pub fn emit_auction_closed(
+    io: &SocketIo,
+    auction_uuid: Uuid,
+    status: AuctionStatus,
+    high_bid: Option<i32>,
+) {
+    let event = AuctionClosed {
+        auction_uuid,
+        status,
+        high_bid,
+    };
+    if let Err(err) = io.emit("auction.closed", &event) {
+        warn!("Unable to emit auction.closed: {:?}", err);
+    }
+}